@@ -1,6 +1,6 @@
 use node_template_runtime::{
 	AccountId, AssetsConfig, AuraConfig, BalancesConfig, GenesisConfig, GrandpaConfig, Signature, SudoConfig,
-	SystemConfig, WASM_BINARY,
+	SystemConfig, TemplateModuleConfig, WASM_BINARY,
 };
 use sc_service::ChainType;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
@@ -178,6 +178,17 @@ fn testnet_genesis(
 			key: Some(root_key),
 		},
 		transaction_payment: Default::default(),
+		treasury: Default::default(),
+		template_module: TemplateModuleConfig {
+			// DOT, ETH, ADA, BTC
+			allowed_assets: vec![1u32, 2u32, 3u32, 4u32],
+			// DOT/ETH, DOT/BTC and ADA/DOT pools, provided by alice
+			pools: vec![
+				(1u32, 2u32, 1_000_000_000u128, 200_000_000u128, alice.clone()),
+				(1u32, 4u32, 1_000_000_000u128, 20_000_000u128, alice.clone()),
+				(3u32, 1u32, 2_000_000_000u128, 500_000_000u128, alice.clone()),
+			],
+		},
 		assets: {
 			AssetsConfig {
 				assets: vec![
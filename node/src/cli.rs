@@ -18,6 +18,9 @@ pub enum Subcommand {
 	/// Build a chain specification.
 	BuildSpec(sc_cli::BuildSpecCmd),
 
+	/// Build a chain specification from a DEX scenario, described in TOML or JSON.
+	BuildScenarioSpec(crate::scenario::BuildScenarioSpecCmd),
+
 	/// Validate blocks.
 	CheckBlock(sc_cli::CheckBlockCmd),
 
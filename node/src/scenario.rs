@@ -0,0 +1,428 @@
+//! Chain specifications of DEX scenarios, described in TOML or JSON.
+//!
+//! A scenario lists the assets of the chain, the accounts endowed with native and assets and the
+//! pools created at genesis, e.g.:
+//!
+//! ```toml
+//! name = "DOT/ETH scenario"
+//! id = "dot_eth"
+//! authorities = ["Alice"]
+//! sudo = "Alice"
+//!
+//! [[assets]]
+//! id = 1
+//! symbol = "DOT"
+//! decimals = 10
+//!
+//! [[assets]]
+//! id = 2
+//! symbol = "ETH"
+//! decimals = 18
+//!
+//! [[accounts]]
+//! account = "Alice"
+//! native = 1_000_000_000_000
+//! tokens = { DOT = 5_000_000_000, ETH = 1_000_000_000 }
+//!
+//! [[pools]]
+//! tokens = ["DOT", "ETH"]
+//! amounts = [1_000_000_000, 200_000_000]
+//! provider = "Alice"
+//! ```
+//!
+//! Accounts are given by seed (`Alice`, `Bob//stash`) or SS58 address, tokens by symbol. TOML only
+//! has 64 bit integers, amounts that don't fit need JSON.
+
+use crate::chain_spec::ChainSpec;
+use node_template_runtime::{
+	AccountId, AssetsConfig, AuraConfig, Balance, BalancesConfig, ExistentialDeposit, GenesisConfig,
+	GrandpaConfig, LpTokenIdStart, MinimumLiquidity, PoolCreationDeposit, SudoConfig, SystemConfig,
+	TemplateModuleConfig, WASM_BINARY,
+};
+use sc_service::ChainType;
+use serde::Deserialize;
+use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+use sp_core::{crypto::Ss58Codec, sr25519, Pair, Public};
+use sp_finality_grandpa::AuthorityId as GrandpaId;
+use sp_runtime::{traits::IdentifyAccount, MultiSigner};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fs,
+	path::PathBuf,
+};
+
+/// Description of a DEX scenario.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+	/// Name of the chain.
+	pub name: String,
+	/// ID of the chain.
+	pub id: String,
+	#[serde(default = "local")]
+	pub chain_type: ChainType,
+	/// Seeds of the initial PoA authorities.
+	pub authorities: Vec<String>,
+	/// Sudo account.
+	pub sudo: String,
+	pub assets: Vec<ScenarioAsset>,
+	#[serde(default)]
+	pub accounts: Vec<ScenarioAccount>,
+	#[serde(default)]
+	pub pools: Vec<ScenarioPool>,
+}
+
+fn local() -> ChainType {
+	ChainType::Local
+}
+
+/// An asset, created and listed in the DEX at genesis.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioAsset {
+	pub id: u32,
+	pub symbol: String,
+	/// Name of the asset, its symbol if not given.
+	pub name: Option<String>,
+	pub decimals: u8,
+	#[serde(default = "min_balance")]
+	pub min_balance: Balance,
+	/// Owner of the asset, the sudo account if not given.
+	pub owner: Option<String>,
+	#[serde(default)]
+	pub sufficient: bool,
+}
+
+fn min_balance() -> Balance {
+	1000
+}
+
+/// An endowed account with its native balance and its balance of each asset, by symbol.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioAccount {
+	pub account: String,
+	#[serde(default = "native")]
+	pub native: Balance,
+	#[serde(default)]
+	pub tokens: BTreeMap<String, Balance>,
+}
+
+fn native() -> Balance {
+	1 << 60
+}
+
+/// A pool created at genesis, with the liquidity its provider deposits.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioPool {
+	pub tokens: [String; 2],
+	pub amounts: [Balance; 2],
+	pub provider: String,
+}
+
+impl Scenario {
+	/// Read a scenario from a `.toml` or `.json` file.
+	pub fn from_file(path: &PathBuf) -> Result<Self, String> {
+		let content = fs::read_to_string(path)
+			.map_err(|e| format!("Error reading scenario {}: {}", path.display(), e))?;
+		match path.extension().and_then(|extension| extension.to_str()) {
+			Some("toml") => toml::from_str(&content).map_err(|e| format!("Invalid scenario: {}", e)),
+			Some("json") =>
+				serde_json::from_str(&content).map_err(|e| format!("Invalid scenario: {}", e)),
+			_ => Err("Scenarios are either .toml or .json files".into()),
+		}
+	}
+
+	/// The chain specification of the scenario.
+	pub fn chain_spec(self) -> Result<ChainSpec, String> {
+		let wasm_binary = WASM_BINARY.ok_or_else(|| "Development wasm not available".to_string())?;
+		// Check the whole scenario up front, the genesis is only built when the spec is written
+		self.genesis(wasm_binary)?;
+		let (name, id, chain_type) = (self.name.clone(), self.id.clone(), self.chain_type.clone());
+
+		Ok(ChainSpec::from_genesis(
+			&name,
+			&id,
+			chain_type,
+			move || self.genesis(wasm_binary).expect("scenario was checked above; qed"),
+			// Bootnodes
+			vec![],
+			// Telemetry
+			None,
+			// Protocol ID
+			None,
+			None,
+			// Properties
+			None,
+			// Extensions
+			None,
+		))
+	}
+
+	fn asset(&self, symbol: &str) -> Result<&ScenarioAsset, String> {
+		self.assets
+			.iter()
+			.find(|asset| asset.symbol == symbol)
+			.ok_or_else(|| format!("Unknown asset {}", symbol))
+	}
+
+	/// The genesis of the scenario, or why building it would fail.
+	fn genesis(&self, wasm_binary: &[u8]) -> Result<GenesisConfig, String> {
+		let root_key = account_id(&self.sudo)?;
+		if self.authorities.is_empty() {
+			return Err("The scenario needs at least one authority".into())
+		}
+		let authorities =
+			self.authorities.iter().map(|seed| authority_keys(seed)).collect::<Result<Vec<_>, _>>()?;
+
+		// Assets, each with its own ID and symbol, below the IDs reserved for lp tokens
+		let mut assets = Vec::new();
+		let mut metadata = Vec::new();
+		let mut ids = BTreeSet::new();
+		let mut symbols = BTreeSet::new();
+		for asset in &self.assets {
+			if !ids.insert(asset.id) {
+				return Err(format!("Duplicate asset ID {}", asset.id))
+			}
+			if !symbols.insert(&asset.symbol) {
+				return Err(format!("Duplicate asset symbol {}", asset.symbol))
+			}
+			if asset.id >= LpTokenIdStart::get() {
+				return Err(format!("Asset ID {} is reserved for lp tokens", asset.id))
+			}
+			if asset.min_balance == 0 {
+				return Err(format!("Asset {} needs a non-zero minimum balance", asset.symbol))
+			}
+			let owner = match &asset.owner {
+				Some(owner) => account_id(owner)?,
+				None => root_key.clone(),
+			};
+			let name = asset.name.clone().unwrap_or_else(|| asset.symbol.clone());
+			assets.push((asset.id, owner, asset.sufficient, asset.min_balance));
+			metadata.push((asset.id, name.into_bytes(), asset.symbol.clone().into_bytes(), asset.decimals));
+		}
+
+		// Endowed accounts, keeping track of what they have left to provide the pools with
+		let mut balances = Vec::new();
+		let mut accounts = Vec::new();
+		let mut native_left = BTreeMap::new();
+		let mut tokens_left = BTreeMap::new();
+		for account in &self.accounts {
+			let who = account_id(&account.account)?;
+			if native_left.insert(who.clone(), account.native).is_some() {
+				return Err(format!("Duplicate account {}", account.account))
+			}
+			if account.native < ExistentialDeposit::get() {
+				return Err(format!("Account {} has less native than the existential deposit", account.account))
+			}
+			for (symbol, amount) in &account.tokens {
+				let asset = self.asset(symbol)?;
+				if *amount < asset.min_balance {
+					return Err(format!("Account {} has less {} than its minimum balance", account.account, symbol))
+				}
+				tokens_left.insert((who.clone(), asset.id), *amount);
+				accounts.push((asset.id, who.clone(), *amount));
+			}
+			balances.push((who, account.native));
+		}
+
+		// Pools of two different assets, each created once by a provider able to pay for it
+		let mut pools = Vec::new();
+		let mut pairs = BTreeSet::new();
+		for pool in &self.pools {
+			let [symbol_a, symbol_b] = &pool.tokens;
+			let [amount_a, amount_b] = pool.amounts;
+			let (asset_a, asset_b) = (self.asset(symbol_a)?, self.asset(symbol_b)?);
+			if asset_a.id == asset_b.id {
+				return Err(format!("Pool of {} with itself", symbol_a))
+			}
+			if !pairs.insert((asset_a.id.min(asset_b.id), asset_a.id.max(asset_b.id))) {
+				return Err(format!("Duplicate pool {}/{}", symbol_a, symbol_b))
+			}
+			let provider = account_id(&pool.provider)?;
+
+			// Creating the pool reserves the deposit and funds the pool's account, keeping the
+			// provider alive
+			let native = native_left
+				.get_mut(&provider)
+				.ok_or_else(|| format!("Provider {} is not an endowed account", pool.provider))?;
+			*native = native
+				.checked_sub(PoolCreationDeposit::get() + ExistentialDeposit::get())
+				.filter(|left| *left >= ExistentialDeposit::get())
+				.ok_or_else(|| {
+					format!("Provider {} can't pay the deposit of the {}/{} pool", pool.provider, symbol_a, symbol_b)
+				})?;
+
+			// The liquidity is transferred keeping the provider's accounts of the assets alive
+			for (asset, amount) in [(asset_a, amount_a), (asset_b, amount_b)] {
+				if amount < asset.min_balance {
+					return Err(format!(
+						"Pool {}/{} gets less {} than its minimum balance",
+						symbol_a, symbol_b, asset.symbol
+					))
+				}
+				let left = tokens_left.entry((provider.clone(), asset.id)).or_default();
+				*left = left.checked_sub(amount).filter(|left| *left >= asset.min_balance).ok_or_else(|| {
+					format!("Provider {} lacks {} for the {}/{} pool", pool.provider, asset.symbol, symbol_a, symbol_b)
+				})?;
+			}
+
+			// The first deposit mints the root of the product of the amounts, minus the locked
+			// minimum liquidity
+			let minimum = MinimumLiquidity::get() + 1;
+			if amount_a.saturating_mul(amount_b) < minimum * minimum {
+				return Err(format!("Pool {}/{} is too small to mint lp tokens", symbol_a, symbol_b))
+			}
+
+			pools.push((asset_a.id, asset_b.id, amount_a, amount_b, provider));
+		}
+
+		Ok(GenesisConfig {
+			system: SystemConfig {
+				// Add Wasm runtime to storage.
+				code: wasm_binary.to_vec(),
+			},
+			balances: BalancesConfig { balances },
+			aura: AuraConfig { authorities: authorities.iter().map(|keys| keys.0.clone()).collect() },
+			grandpa: GrandpaConfig { authorities: authorities.iter().map(|keys| (keys.1.clone(), 1)).collect() },
+			sudo: SudoConfig {
+				// Assign network admin rights.
+				key: Some(root_key),
+			},
+			transaction_payment: Default::default(),
+			treasury: Default::default(),
+			template_module: TemplateModuleConfig {
+				allowed_assets: self.assets.iter().map(|asset| asset.id).collect(),
+				pools,
+			},
+			assets: AssetsConfig { assets, metadata, accounts },
+		})
+	}
+}
+
+/// Aura and Grandpa keys of an authority seed.
+fn authority_keys(seed: &str) -> Result<(AuraId, GrandpaId), String> {
+	Ok((public_from_seed::<AuraId>(seed)?, public_from_seed::<GrandpaId>(seed)?))
+}
+
+fn public_from_seed<TPublic: Public>(seed: &str) -> Result<<TPublic::Pair as Pair>::Public, String> {
+	TPublic::Pair::from_string(&format!("//{}", seed), None)
+		.map(|pair| pair.public())
+		.map_err(|_| format!("Invalid authority {}", seed))
+}
+
+/// Account of an SS58 address, or of a seed otherwise.
+fn account_id(account: &str) -> Result<AccountId, String> {
+	if let Ok(account_id) = AccountId::from_ss58check(account) {
+		return Ok(account_id)
+	}
+	let pair = sr25519::Pair::from_string(&format!("//{}", account), None)
+		.map_err(|_| format!("Invalid account {}", account))?;
+	Ok(MultiSigner::from(pair.public()).into_account())
+}
+
+/// The `build-scenario-spec` command used to build a chain specification from a scenario.
+#[derive(Debug, clap::Parser)]
+pub struct BuildScenarioSpecCmd {
+	/// Scenario to build the chain specification of, a `.toml` or `.json` file.
+	#[clap(parse(from_os_str))]
+	pub scenario: PathBuf,
+
+	/// Force raw genesis storage output.
+	#[clap(long)]
+	pub raw: bool,
+
+	/// File to write the chain specification to, instead of stdout.
+	#[clap(long, short, parse(from_os_str))]
+	pub output: Option<PathBuf>,
+}
+
+impl BuildScenarioSpecCmd {
+	/// Run the build-scenario-spec command.
+	pub fn run(&self) -> sc_cli::Result<()> {
+		let spec = Scenario::from_file(&self.scenario)?.chain_spec()?;
+		let json = sc_service::ChainSpec::as_json(&spec, self.raw)?;
+		match &self.output {
+			Some(output) => fs::write(output, json)?,
+			None => println!("{}", json),
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SCENARIO: &str = r#"
+name = "DOT/ETH scenario"
+id = "dot_eth"
+authorities = ["Alice"]
+sudo = "Alice"
+
+[[assets]]
+id = 1
+symbol = "DOT"
+decimals = 10
+
+[[assets]]
+id = 2
+symbol = "ETH"
+decimals = 18
+
+[[accounts]]
+account = "Alice"
+native = 1_000_000_000_000
+tokens = { DOT = 5_000_000_000, ETH = 1_000_000_000 }
+
+[[pools]]
+tokens = ["DOT", "ETH"]
+amounts = [1_000_000_000, 200_000_000]
+provider = "Alice"
+"#;
+
+	fn genesis(scenario: &str) -> Result<GenesisConfig, String> {
+		toml::from_str::<Scenario>(scenario).map_err(|e| e.to_string())?.genesis(&[])
+	}
+
+	#[test]
+	fn test_scenario() {
+		let genesis = genesis(SCENARIO).unwrap();
+		let alice = account_id("Alice").unwrap();
+		assert_eq!(genesis.template_module.allowed_assets, vec![1, 2]);
+		assert_eq!(genesis.template_module.pools, vec![(1, 2, 1_000_000_000, 200_000_000, alice.clone())]);
+		assert_eq!(genesis.assets.accounts, vec![(1, alice.clone(), 5_000_000_000), (2, alice, 1_000_000_000)]);
+	}
+
+	#[test]
+	fn test_invalid_scenarios() {
+		// Unknown field
+		assert!(genesis(&format!("{}\nfee = 3", SCENARIO)).is_err());
+
+		// Invalid authority
+		assert!(genesis(&SCENARIO.replace(r#"authorities = ["Alice"]"#, r#"authorities = [""]"#)).is_err());
+
+		// Duplicate asset ID and symbol
+		assert!(genesis(&SCENARIO.replace("id = 2", "id = 1")).is_err());
+		assert!(genesis(&SCENARIO.replace(r#"symbol = "ETH""#, r#"symbol = "DOT""#)).is_err());
+
+		// Asset ID reserved for lp tokens
+		assert!(genesis(&SCENARIO.replace("id = 2", &format!("id = {}", LpTokenIdStart::get()))).is_err());
+
+		// Pool with identical tokens, and the same pool twice
+		assert!(genesis(&SCENARIO.replace(r#"tokens = ["DOT", "ETH"]"#, r#"tokens = ["DOT", "DOT"]"#)).is_err());
+		assert!(genesis(&format!(
+			"{}\n[[pools]]\ntokens = [\"ETH\", \"DOT\"]\namounts = [1_000_000, 1_000_000]\nprovider = \"Alice\"",
+			SCENARIO
+		))
+		.is_err());
+
+		// Provider without enough tokens, or native for the deposit and the pool's account
+		assert!(genesis(&SCENARIO.replace("ETH = 1_000_000_000", "ETH = 200_000_000")).is_err());
+		assert!(genesis(&SCENARIO.replace("native = 1_000_000_000_000", "native = 101_000")).is_err());
+
+		// Provider that isn't endowed
+		assert!(genesis(&SCENARIO.replace(r#"provider = "Alice""#, r#"provider = "Bob""#)).is_err());
+	}
+}
@@ -1,58 +1,240 @@
 use crate::*;
-use frame_support::sp_runtime::traits::AtLeast32Bit;
-use sp_arithmetic::traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, IntegerSquareRoot};
+use frame_support::sp_runtime::traits::AtLeast32BitUnsigned;
+use sp_arithmetic::{PerThing, Permill};
+use sp_arithmetic::traits::{CheckedSub, Zero};
+use sp_core::{U256, U512};
 pub struct DexPricer;
 
-const PRECISION: u32 = 1_000_000_000;
+/// Why an amount couldn't be priced.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PricerError {
+	/// An amount doesn't fit in the balance type.
+	Overflow,
+	/// The pool has no reserves or no lp tokens to price against.
+	ZeroLiquidity,
+	/// Nothing goes into the pool.
+	InsufficientInputAmount,
+	/// The amount going in is too small to get anything out.
+	InsufficientOutputAmount,
+	/// The trade would take a pool's entire reserve of a token.
+	ReserveDrained,
+	/// The trade would make k decrease.
+	InvariantViolated,
+}
 
+/// Direction in which a division is rounded.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Rounding {
+	Down,
+	Up,
+}
+
+// All products are taken in 256 bits, so any two balances can be multiplied without
+// overflowing. Only results that don't fit in the balance type are rejected.
+//
+// Rounding always favours the pool: whatever leaves the pool or is minted against it (lp
+// tokens, swap output, withdrawn tokens) is rounded down, whatever goes into it (swap input,
+// swap fees) is rounded up.
 impl DexPricer {
 
-	pub fn new_pool_function<T: IntegerSquareRoot + CheckedAdd + CheckedMul + CheckedDiv + From<u32>>(
+	/// Lp tokens for the first deposit into a pool: sqrt(a * b), rounded down.
+	pub fn new_pool_function<T: AtLeast32BitUnsigned + Copy>(
 		a: T,
 		b: T,
-	) -> Option<T> {
-		let k = a.checked_mul(&b);
-		match k {
-			Some(k) => k.integer_sqrt_checked(),
-			None => None,
-		}
+	) -> Result<T, PricerError> {
+		let k = Self::to_u256(a)?.checked_mul(Self::to_u256(b)?).ok_or(PricerError::Overflow)?;
+		Self::from_u256(k.integer_sqrt())
 	}
 	
-	pub fn existing_pool_function<T: IntegerSquareRoot + CheckedAdd + CheckedMul + CheckedDiv + From<u32>>(
-		wallet_a: T,
-		pool_a: T,
+	/// Lp reward for a deposit of `wallet` tokens into a pool holding `pool`. The limiting
+	/// side decides, so a lopsided deposit doesn't earn more than its smallest share.
+	pub fn existing_pool_function<T: AtLeast32BitUnsigned + Copy>(
+		wallet: (T, T),
+		pool: (T, T),
 		lp_minted: T,
-	) -> Option<T> {
-		let incr_wallet_a = wallet_a.checked_mul(&PRECISION.into());
-		let share = incr_wallet_a?.checked_div(&pool_a);
-		match share {
-			Some(x) => x.checked_mul(&lp_minted)?.checked_div(&PRECISION.into()),
-			None => None,
+	) -> Result<T, PricerError> {
+		let lp_a = Self::mul_div(wallet.0, lp_minted, pool.0, Rounding::Down)?;
+		let lp_b = Self::mul_div(wallet.1, lp_minted, pool.1, Rounding::Down)?;
+		Ok(lp_a.min(lp_b))
+	}
+
+	/// Amount of token b worth `amount_a` of token a at the ratio of the pool's `reserves`,
+	/// rounded down.
+	pub fn quote<T: AtLeast32BitUnsigned + Copy>(amount_a: T, reserves: (T, T)) -> Result<T, PricerError> {
+		Self::mul_div(amount_a, reserves.1, reserves.0, Rounding::Down)
+	}
+
+	/// The largest deposit that matches the ratio of the pool's `reserves` without exceeding the
+	/// `desired` amounts of either token.
+	pub fn optimal_deposit<T: AtLeast32BitUnsigned + Copy>(
+		desired: (T, T),
+		reserves: (T, T),
+	) -> Result<(T, T), PricerError> {
+		if reserves.0.is_zero() || reserves.1.is_zero() {
+			return Err(PricerError::ZeroLiquidity);
+		}
+		// Quotes too large for the balance type are more than desired anyway
+		let optimal_b = Self::quote(desired.0, reserves).ok();
+		if let Some(optimal_b) = optimal_b.filter(|b| *b <= desired.1) {
+			return Ok((desired.0, optimal_b));
+		}
+		let optimal_a = Self::quote(desired.1, (reserves.1, reserves.0))?;
+		if optimal_a <= desired.0 {
+			Ok((optimal_a, desired.1))
+		} else {
+			Err(PricerError::Overflow)
 		}
 	}
 
-	pub fn liquidity_reward<T: IntegerSquareRoot + CheckedAdd + CheckedMul + CheckedDiv + From<u32>>(
+	/// Share of the pool's `pool` tokens for `lp_tokens` of the `lp_minted` lp tokens.
+	pub fn liquidity_reward<T: AtLeast32BitUnsigned + Copy>(
 		lp_tokens: T,
 		lp_minted: T,
 		pool: T,
-	) -> Option<T> {
-		let incr_lp_tokens = lp_tokens.checked_mul(&PRECISION.into());	
-		let share = incr_lp_tokens?.checked_div(&lp_minted);
-		match share {
-			Some(x) => x.checked_mul(&pool)?.checked_div(&PRECISION.into()),
-			None => return None,
-		}
+	) -> Result<T, PricerError> {
+		Self::mul_div(lp_tokens, pool, lp_minted, Rounding::Down)
 	}
 
-	pub fn swap<T: IntegerSquareRoot + CheckedAdd + CheckedMul + CheckedDiv + From<u32>>(
+	/// Constant-product (x * y = k) swap. `liquidity` holds the pool's reserves as
+	/// `(reserve_in, reserve_out)`. The `fee` is taken from `tokens` before pricing and
+	/// stays in the pool. Returns `(amount_out, fee_amount)`.
+	pub fn swap<T: AtLeast32BitUnsigned + Copy>(
 		tokens: T,
 		liquidity: (T, T),
-	) -> Option<T> {
-		let liquidity_ratio = liquidity.0.checked_mul(&PRECISION.into())?.checked_div(&liquidity.1);
-		match liquidity_ratio {
-			Some(liquidity_ratio) => tokens.checked_mul(&PRECISION.into())?.checked_div(&liquidity_ratio),
-			None => None,
+		fee: Permill,
+	) -> Result<(T, T), PricerError> {
+		let (reserve_in, reserve_out) = liquidity;
+		if tokens.is_zero() {
+			return Err(PricerError::InsufficientInputAmount);
+		}
+		if reserve_in.is_zero() || reserve_out.is_zero() {
+			return Err(PricerError::ZeroLiquidity);
+		}
+
+		// Only what is left after the fee is priced
+		let fee_amount = fee.mul_ceil(tokens);
+		let tokens_after_fee = tokens.checked_sub(&fee_amount).ok_or(PricerError::Overflow)?;
+
+		// amount_out = tokens_after_fee * reserve_out / (reserve_in + tokens_after_fee)
+		let reserve_in = Self::to_u256(reserve_in)?;
+		let reserve_out = Self::to_u256(reserve_out)?;
+		let tokens_after_fee = Self::to_u256(tokens_after_fee)?;
+		let new_reserve_in = reserve_in.checked_add(tokens_after_fee).ok_or(PricerError::Overflow)?;
+		let amount_out = Self::div(
+			tokens_after_fee.checked_mul(reserve_out).ok_or(PricerError::Overflow)?,
+			new_reserve_in,
+			Rounding::Down,
+		)?;
+		if amount_out.is_zero() {
+			return Err(PricerError::InsufficientOutputAmount);
+		}
+
+		// The invariant may never decrease, rounding always ends up in the pool
+		let new_reserve_out = reserve_out.checked_sub(amount_out).ok_or(PricerError::ReserveDrained)?;
+		Self::ensure_k((reserve_in, reserve_out), (new_reserve_in, new_reserve_out))?;
+		Ok((Self::from_u256(amount_out)?, fee_amount))
+	}
+
+	/// Inverse of `swap`: the amount of input tokens needed to get exactly `amount_out` from a
+	/// pool with `liquidity` as `(reserve_in, reserve_out)`. Returns `(amount_in, fee_amount)`,
+	/// both rounded up so the pool never loses out.
+	pub fn swap_exact_out<T: AtLeast32BitUnsigned + Copy>(
+		amount_out: T,
+		liquidity: (T, T),
+		fee: Permill,
+	) -> Result<(T, T), PricerError> {
+		let (reserve_in, reserve_out) = liquidity;
+		if amount_out.is_zero() {
+			return Err(PricerError::InsufficientOutputAmount);
+		}
+		if reserve_in.is_zero() || reserve_out.is_zero() {
+			return Err(PricerError::ZeroLiquidity);
+		}
+		if amount_out >= reserve_out {
+			return Err(PricerError::ReserveDrained);
+		}
+
+		// tokens_after_fee = reserve_in * amount_out / (reserve_out - amount_out)
+		let reserve_in = Self::to_u256(reserve_in)?;
+		let reserve_out = Self::to_u256(reserve_out)?;
+		let amount_out = Self::to_u256(amount_out)?;
+		let new_reserve_out = reserve_out.checked_sub(amount_out).ok_or(PricerError::ReserveDrained)?;
+		let tokens_after_fee = Self::div(
+			reserve_in.checked_mul(amount_out).ok_or(PricerError::Overflow)?,
+			new_reserve_out,
+			Rounding::Up,
+		)?;
+
+		// amount_in = tokens_after_fee / (1 - fee)
+		let accuracy = U256::from(Permill::one().deconstruct());
+		let fee_parts = U256::from(fee.deconstruct());
+		let amount_in = Self::div(
+			tokens_after_fee.checked_mul(accuracy).ok_or(PricerError::Overflow)?,
+			accuracy.checked_sub(fee_parts).ok_or(PricerError::Overflow)?,
+			Rounding::Up,
+		)?;
+
+		// The invariant may never decrease
+		let new_reserve_in = reserve_in.checked_add(tokens_after_fee).ok_or(PricerError::Overflow)?;
+		Self::ensure_k((reserve_in, reserve_out), (new_reserve_in, new_reserve_out))?;
+		let amount_in: T = Self::from_u256(amount_in)?;
+		let fee_amount = amount_in.checked_sub(&Self::from_u256(tokens_after_fee)?).ok_or(PricerError::Overflow)?;
+		Ok((amount_in, fee_amount))
+	}
+
+	/// Uniswap v2 style fee switch. The amount of lp tokens to mint for the protocol so it
+	/// owns `share` of the growth of sqrt(k) since `root_k_last`.
+	pub fn protocol_fee<T: AtLeast32BitUnsigned + Copy>(
+		lp_minted: T,
+		root_k: T,
+		root_k_last: T,
+		share: Permill,
+	) -> Result<T, PricerError> {
+		if root_k <= root_k_last {
+			return Ok(T::zero());
+		}
+		// lp_minted * protocol_growth / (root_k - protocol_growth)
+		let protocol_growth = share * root_k.checked_sub(&root_k_last).ok_or(PricerError::Overflow)?;
+		let denominator = root_k.checked_sub(&protocol_growth).ok_or(PricerError::Overflow)?;
+		Self::mul_div(lp_minted, protocol_growth, denominator, Rounding::Down)
+	}
+
+	/// a * b / c, rounded in the given direction.
+	fn mul_div<T: AtLeast32BitUnsigned + Copy>(a: T, b: T, c: T, rounding: Rounding) -> Result<T, PricerError> {
+		let product = Self::to_u256(a)?.checked_mul(Self::to_u256(b)?).ok_or(PricerError::Overflow)?;
+		Self::from_u256(Self::div(product, Self::to_u256(c)?, rounding)?)
+	}
+
+	/// a / b, rounded in the given direction. Dividing by nothing means there is no liquidity.
+	fn div(a: U256, b: U256, rounding: Rounding) -> Result<U256, PricerError> {
+		let quotient = a.checked_div(b).ok_or(PricerError::ZeroLiquidity)?;
+		if rounding == Rounding::Down || (a % b).is_zero() {
+			Ok(quotient)
+		} else {
+			quotient.checked_add(U256::one()).ok_or(PricerError::Overflow)
+		}
+	}
+
+	fn ensure_k(before: (U256, U256), after: (U256, U256)) -> Result<(), PricerError> {
+		// The reserves fit in 256 bits, so k always fits in 512 bits
+		let k_before = U512::from(before.0).checked_mul(U512::from(before.1)).ok_or(PricerError::Overflow)?;
+		let k_after = U512::from(after.0).checked_mul(U512::from(after.1)).ok_or(PricerError::Overflow)?;
+		if k_after < k_before {
+			return Err(PricerError::InvariantViolated);
+		}
+		Ok(())
+	}
+
+	fn to_u256<T: AtLeast32BitUnsigned>(value: T) -> Result<U256, PricerError> {
+		let value: u128 = value.try_into().map_err(|_| PricerError::Overflow)?;
+		Ok(U256::from(value))
+	}
+
+	fn from_u256<T: AtLeast32BitUnsigned>(value: U256) -> Result<T, PricerError> {
+		if value > U256::from(u128::MAX) {
+			return Err(PricerError::Overflow);
 		}
+		value.low_u128().try_into().map_err(|_| PricerError::Overflow)
 	}
 
-}
\ No newline at end of file
+}
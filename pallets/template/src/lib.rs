@@ -15,33 +15,115 @@ mod help_test;
 #[cfg(feature = "runtime-benchmarks")]
 mod benchmarking;
 
+pub mod migrations;
+pub mod runtime_api;
+
 #[frame_support::pallet]
 pub mod pallet {
-	use crate::dex_pricer::{DexPricer};
+	use crate::dex_pricer::{DexPricer, PricerError};
+	use crate::migrations::Migrations;
 	use codec::MaxEncodedLen;
 	use frame_support::pallet_prelude::*;
 	use frame_system::pallet_prelude::*;
 	use frame_support::{PalletId, Hashable};
 	use crate::pallet::vec::Vec;
 	use scale_info::prelude::vec;
-	use sp_runtime::traits::{AccountIdConversion, AtLeast32Bit};
+	use sp_runtime::traits::{AccountIdConversion, AtLeast32Bit, Bounded, One, Zero};
+	#[cfg(any(feature = "try-runtime", test))]
+	use sp_runtime::SaturatedConversion;
+	#[cfg(any(feature = "try-runtime", test))]
+	use sp_core::U256;
+	#[cfg(any(feature = "try-runtime", test))]
+	use frame_support::storage::unhashed;
+	#[cfg(feature = "try-runtime")]
+	use frame_support::traits::OnRuntimeUpgradeHelpersExt;
+	use sp_arithmetic::{PerThing, Permill};
 	use frame_support::traits::tokens::fungibles::{Inspect, Transfer, Mutate, Create};
-	use frame_support::traits::tokens::currency::Currency;
-	use sp_arithmetic::traits::{CheckedAdd, CheckedMul, CheckedDiv, IntegerSquareRoot}; 
-
-	type TokenIdOf<T: Config> = <T::Tokens as Inspect<T::AccountId>>::AssetId;
-	type BalanceOf<T: Config> = <T::Tokens as Inspect<T::AccountId>>::Balance;
+	use frame_support::traits::tokens::currency::{Currency, ReservableCurrency};
+	use frame_support::traits::{ExistenceRequirement, OnRuntimeUpgrade};
+	use sp_arithmetic::traits::{CheckedAdd, CheckedMul, CheckedDiv, CheckedSub, IntegerSquareRoot, Saturating}; 
+
+	pub(super) type TokenIdOf<T: Config> = <T::Tokens as Inspect<T::AccountId>>::AssetId;
+	pub(super) type BalanceOf<T: Config> = <T::Tokens as Inspect<T::AccountId>>::Balance;
+	pub(super) type NativeBalanceOf<T: Config> = <T::Balances as Currency<T::AccountId>>::Balance;
+	pub type PoolInfoOf<T> = PoolInfo<
+		<T as frame_system::Config>::AccountId,
+		TokenIdOf<T>,
+		BalanceOf<T>,
+		<T as frame_system::Config>::BlockNumber,
+	>;
 	
 	#[pallet::config]
 	pub trait Config: frame_system::Config {
 		type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
-		type Tokens: Inspect<Self::AccountId>
+		type Tokens: Inspect<Self::AccountId, AssetId = Self::AssetId, Balance = Self::AssetBalance>
 		+ Transfer<Self::AccountId>
 		+ Mutate<Self::AccountId>
 		+ Create<Self::AccountId>;
-		type Balances: Currency<Self::AccountId>;
+		/// Identifier of the tokens, the asset id of `Tokens`.
+		type AssetId: Member + Parameter + Copy + MaybeSerializeDeserialize + MaxEncodedLen;
+		/// Balance of the tokens, the balance of `Tokens`.
+		type AssetBalance: frame_support::traits::tokens::Balance + MaybeSerializeDeserialize;
+		type Balances: ReservableCurrency<Self::AccountId>;
 		type PalletId: Get<PalletId>;
-		type MaxLiqProviders: Get<u32>;	
+		/// Fee taken from the input of every swap. It stays in the pool for the liquidity providers.
+		/// Below 100%, or no input would buy an exact output.
+		#[pallet::constant]
+		type SwapFee: Get<Permill>;
+		/// Deposit reserved from the creator of a pool.
+		#[pallet::constant]
+		type PoolCreationDeposit: Get<NativeBalanceOf<Self>>;
+		/// Lp tokens locked in every pool by its first deposit, so its lp tokens can't be
+		/// inflated to round later deposits down to nothing.
+		#[pallet::constant]
+		type MinimumLiquidity: Get<BalanceOf<Self>>;
+		/// First asset ID of the lp tokens, the IDs from here on are reserved for them.
+		#[pallet::constant]
+		type LpTokenIdStart: Get<Self::AssetId>;
+		/// Maximum number of lp token IDs taken by other assets that creating a pool skips, every
+		/// skipped ID costs a read.
+		#[pallet::constant]
+		type MaxLpTokenIdSkips: Get<u32>;
+		/// Maximum number of tokens in the path of a multi-hop swap.
+		#[pallet::constant]
+		type MaxPathLength: Get<u32>;
+		/// Account (the treasury) that receives the protocol's share of the liquidity fees.
+		type ProtocolFeeReceiver: Get<Self::AccountId>;
+		/// Origin that manages the allowed assets and the protocol fee.
+		type AdminOrigin: EnsureOrigin<Self::Origin>;
+	}
+
+	/// Whether a listed asset can be used for everything or only to withdraw liquidity.
+	#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug, TypeInfo, MaxEncodedLen)]
+	pub enum AssetStatus {
+		/// Can be deposited, swapped and withdrawn.
+		Active,
+		/// Can only be withdrawn.
+		WithdrawOnly,
+	}
+
+	/// State of a pool. The reserves and the lp supply are tracked here, so tokens sent to the
+	/// pool's account directly don't change its price.
+	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, TypeInfo, MaxEncodedLen)]
+	pub struct PoolInfo<AccountId, AssetId, Balance, BlockNumber> {
+		/// First token of the sorted token pair.
+		pub token_a: AssetId,
+		/// Second token of the sorted token pair.
+		pub token_b: AssetId,
+		/// Account holding the pool's tokens.
+		pub account: AccountId,
+		/// Amount of `token_a` in the pool.
+		pub reserve_a: Balance,
+		/// Amount of `token_b` in the pool.
+		pub reserve_b: Balance,
+		/// Asset ID of the pool's lp token.
+		pub lp_token: AssetId,
+		/// Amount of lp tokens given out.
+		pub lp_supply: Balance,
+		/// Fee taken from the input of every swap.
+		pub fee: Permill,
+		/// Block in which the pool was created.
+		pub created_at: BlockNumber,
 	}
 
 	#[derive(Encode, Decode, TypeInfo, DebugNoBound, CloneNoBound, EqNoBound, PartialEqNoBound)]
@@ -51,6 +133,7 @@ pub mod pallet {
 		tokenpair_id: [u8; 16],
 		quantity_token_a: BalanceOf<T>,
 		quantity_token_b: BalanceOf<T>,
+		min_lp_out: BalanceOf<T>,
 	}
 
 	#[derive(Encode, Decode, TypeInfo, DebugNoBound, CloneNoBound, EqNoBound, PartialEqNoBound)]
@@ -59,41 +142,180 @@ pub mod pallet {
 		tokenpair: Vec<TokenIdOf<T>>,
 		tokenpair_id: [u8; 16],
 		lp_token: TokenIdOf<T>,
+		lp_amount: BalanceOf<T>,
+		min_token_a: BalanceOf<T>,
+		min_token_b: BalanceOf<T>,
 	}
 
+	/// Version of the storage layout, see `migrations`.
+	const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
+
 	#[pallet::pallet]
 	#[pallet::generate_store(pub(super) trait Store)]
+	#[pallet::storage_version(STORAGE_VERSION)]
 	pub struct Pallet<T>(_);
 	
 	// STORAGE
+	/// All pools, by token pair ID.
+	#[pallet::storage]
+	pub(super) type Pools<T: Config> = StorageMap<_, Blake2_128Concat, [u8; 16], PoolInfoOf<T>>;
+
+	/// Number of pools of every asset, an asset with pools can't be delisted.
 	#[pallet::storage]
-	pub(super) type AllPools<T: Config> = StorageMap<_, Blake2_128Concat, [u8; 16], T::AccountId>;
+	pub(super) type AssetPools<T: Config> = StorageMap<_, Blake2_128Concat, TokenIdOf<T>, u32, ValueQuery>;
 
+	/// Asset ID of the lp token of the next pool, `LpTokenIdStart` before the first pool.
 	#[pallet::storage]
-	pub(super) type LiquidityProviders<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, BoundedVec<T::AccountId, T::MaxLiqProviders>, ValueQuery>;
+	pub(super) type NextLpTokenId<T: Config> = StorageValue<_, TokenIdOf<T>>;
+
+	/// Assets that can be used in the pools.
+	#[pallet::storage]
+	pub(super) type AllowedAssets<T: Config> = StorageMap<_, Blake2_128Concat, TokenIdOf<T>, AssetStatus>;
+
+	/// Share of the liquidity fees minted to the `ProtocolFeeReceiver`, zero means switched off.
+	#[pallet::storage]
+	pub(super) type ProtocolFee<T> = StorageValue<_, Permill, ValueQuery>;
+
+	/// sqrt(k) of every pool after its last deposit or withdrawal, only kept while the protocol
+	/// fee is switched on.
+	#[pallet::storage]
+	pub(super) type LastRootK<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, BalanceOf<T>, ValueQuery>;
+
+	// GENESIS
+	#[pallet::genesis_config]
+	pub struct GenesisConfig<T: Config> {
+		/// Assets that can be used in the pools from the start.
+		pub allowed_assets: Vec<TokenIdOf<T>>,
+		/// Pools created from the start: the token pair, the liquidity of each token and the
+		/// account that provides it. The provider pays the pool's deposit and gets its lp tokens,
+		/// so it needs the native and both tokens at genesis.
+		pub pools: Vec<(TokenIdOf<T>, TokenIdOf<T>, BalanceOf<T>, BalanceOf<T>, T::AccountId)>,
+	}
+
+	#[cfg(feature = "std")]
+	impl<T: Config> Default for GenesisConfig<T> {
+		fn default() -> Self {
+			Self { allowed_assets: Vec::new(), pools: Vec::new() }
+		}
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config> GenesisBuild<T> for GenesisConfig<T>
+		where TokenIdOf<T>: AtLeast32Bit + Encode + MaxEncodedLen + CheckedAdd + CheckedMul + CheckedDiv + IntegerSquareRoot {
+		fn build(&self) {
+			for asset in &self.allowed_assets {
+				// Like `add_asset`, the range of the lp tokens can't be listed
+				assert!(*asset < T::LpTokenIdStart::get(), "genesis assets can't use the IDs reserved for lp tokens");
+				AllowedAssets::<T>::insert(asset, AssetStatus::Active);
+			}
+
+			// Create every pool and deposit its liquidity as the provider would
+			for (token_a, token_b, amount_a, amount_b, provider) in &self.pools {
+				let origin: OriginFor<T> = frame_system::RawOrigin::Signed(provider.clone()).into();
+				Pallet::<T>::create_pool(origin.clone(), *token_a, *token_b)
+					.expect("genesis pools are pairs of allowed assets");
+				Pallet::<T>::deposit_liquidity(
+					origin,
+					*token_a,
+					*token_b,
+					*amount_a,
+					*amount_b,
+					Zero::zero(),
+					T::BlockNumber::max_value(),
+				)
+				.expect("genesis providers hold the liquidity of their pools");
+			}
+		}
+	}
 
 	// EVENTS
+	/// Events about a pool are indexed by a topic keyed by the pool's account, see
+	/// `Pallet::pool_topic`.
 	#[pallet::event]
 	#[pallet::generate_deposit(pub(super) fn deposit_event)]
 	pub enum Event<T: Config> {
+		// PoolCreated
+		PoolCreated {
+			creator: T::AccountId,
+			pool: T::AccountId,
+			token_a: TokenIdOf<T>,
+			token_b: TokenIdOf<T>,
+			lp_token: TokenIdOf<T>,
+		},
 		// LiquidityWithdrawn
 		LiquidityWithdrawn {
-			from: T::AccountId,
-			to: T::AccountId,
+			who: T::AccountId,
+			pool: T::AccountId,
+			token_a: TokenIdOf<T>,
+			token_b: TokenIdOf<T>,
+			amount_a: BalanceOf<T>,
+			amount_b: BalanceOf<T>,
 			lp_token: TokenIdOf<T>,
+			lp_burned: BalanceOf<T>,
+			reserve_a: BalanceOf<T>,
+			reserve_b: BalanceOf<T>,
 		},
 		// LiquidityDeposited
 		LiquidityDeposited {
-			from: T::AccountId,
-			to: T::AccountId,
+			who: T::AccountId,
+			pool: T::AccountId,
 			token_a: TokenIdOf<T>,
 			token_b: TokenIdOf<T>,
+			amount_a: BalanceOf<T>,
+			amount_b: BalanceOf<T>,
+			lp_token: TokenIdOf<T>,
+			lp_minted: BalanceOf<T>,
+			reserve_a: BalanceOf<T>,
+			reserve_b: BalanceOf<T>,
 		},
 		// SwapAccured
 		SwapOccured {
-			from: T::AccountId,
+			who: T::AccountId,
+			pool: T::AccountId,
+			token_in: TokenIdOf<T>,
+			token_out: TokenIdOf<T>,
+			amount_in: BalanceOf<T>,
+			amount_out: BalanceOf<T>,
+			fee: BalanceOf<T>,
+			reserve_in: BalanceOf<T>,
+			reserve_out: BalanceOf<T>,
+		},
+		// Skimmed
+		Skimmed {
+			pool: T::AccountId,
 			to: T::AccountId,
-		}
+			amount_a: BalanceOf<T>,
+			amount_b: BalanceOf<T>,
+		},
+		// Synced
+		Synced {
+			pool: T::AccountId,
+			reserve_a: BalanceOf<T>,
+			reserve_b: BalanceOf<T>,
+		},
+		// ProtocolFeeSet
+		ProtocolFeeSet {
+			share: Permill,
+		},
+		// ProtocolFeeMinted
+		ProtocolFeeMinted {
+			pool: T::AccountId,
+			lp_token: TokenIdOf<T>,
+			amount: BalanceOf<T>,
+		},
+		// AssetListed
+		AssetListed {
+			asset: TokenIdOf<T>,
+		},
+		// AssetDelisted
+		AssetDelisted {
+			asset: TokenIdOf<T>,
+		},
+		// AssetWithdrawOnlySet
+		AssetWithdrawOnlySet {
+			asset: TokenIdOf<T>,
+			withdraw_only: bool,
+		},
 	}
 
 	// ERROR
@@ -109,16 +331,98 @@ pub mod pallet {
 		NotEnoughFundsTokenB,
 		/// For withdrawing liquidity from a pool that doesn't exist.
 		PoolNotFound,
-		/// To many liquidity providers which shouldn't be possible.
-		LiqProvidersOverflow,
 		/// Can't provide liquidity with this token. 
 		InvalidToken,
 		/// Defensive error.
 		NoTokens,
-		/// Wallet has not provided liquidity to this pool
-		NoLiquidityProvided,
-		/// Math problem
-		MathProblem,
+		/// When an amount doesn't fit in the balance type.
+		Overflow,
+		/// When a pool has no reserves or no lp tokens to price against.
+		ZeroLiquidity,
+		/// When nothing would go into a pool.
+		InsufficientInputAmount,
+		/// When the amount going into a pool is too small to get anything out.
+		InsufficientOutputAmount,
+		/// When a swap would take a pool's entire reserve of a token.
+		ReserveDrained,
+		/// When the outcome is less than the minimum the user asked for.
+		SlippageExceeded,
+		/// When the extrinsic is included after the deadline the user set.
+		DeadlinePassed,
+		/// When a swap path is too short or visits a token twice.
+		InvalidPath,
+		/// Token can only be used to withdraw liquidity.
+		TokenWithdrawOnly,
+		/// When listing an asset that is already listed.
+		AssetAlreadyListed,
+		/// When changing an asset that isn't listed.
+		AssetNotListed,
+		/// When creating a pool for a token pair that already has one.
+		PoolAlreadyExists,
+		/// When listing an asset with an ID from the range reserved for lp tokens.
+		AssetIdReserved,
+		/// When all the asset IDs reserved for lp tokens are used, or more than
+		/// `MaxLpTokenIdSkips` of the next ones are taken by other assets.
+		LpTokenIdsExhausted,
+		/// When a deposit is too small to get any lp tokens.
+		InsufficientLiquidityMinted,
+		/// When a withdrawal would leave less than the minimum liquidity in a pool.
+		LiquidityBelowMinimum,
+		/// When a trade would make a pool's k decrease.
+		InvariantViolated,
+		/// When delisting an asset that pools still hold, it can only be made withdraw-only.
+		AssetInUse,
+	}
+
+	impl<T> From<PricerError> for Error<T> {
+		fn from(error: PricerError) -> Self {
+			match error {
+				PricerError::Overflow => Error::<T>::Overflow,
+				PricerError::ZeroLiquidity => Error::<T>::ZeroLiquidity,
+				PricerError::InsufficientInputAmount => Error::<T>::InsufficientInputAmount,
+				PricerError::InsufficientOutputAmount => Error::<T>::InsufficientOutputAmount,
+				PricerError::ReserveDrained => Error::<T>::ReserveDrained,
+				PricerError::InvariantViolated => Error::<T>::InvariantViolated,
+			}
+		}
+	}
+
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T>
+		where TokenIdOf<T>: AtLeast32Bit {
+		fn on_runtime_upgrade() -> Weight {
+			// Bring the storage up to `STORAGE_VERSION`
+			Migrations::<T>::on_runtime_upgrade()
+		}
+
+		fn integrity_test() {
+			// Exact output swaps divide by what's left after the fee
+			assert!(T::SwapFee::get() < Permill::one(), "the swap fee must be below 100%");
+		}
+
+		#[cfg(any(feature = "try-runtime", test))]
+		fn on_finalize(_n: BlockNumberFor<T>) {
+			// Every pool has to be consistent at the end of every block, and k per lp token can't
+			// have dropped since the end of the last one
+			let previous = unhashed::get::<PoolSnapshot<T>>(POOL_SNAPSHOT_KEY).unwrap_or_default();
+			Self::do_try_state(&previous).expect("pool invariants hold at the end of every block");
+			unhashed::put(POOL_SNAPSHOT_KEY, &Self::pool_snapshot().expect("pools can be snapshotted"));
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn pre_upgrade() -> Result<(), &'static str> {
+			Migrations::<T>::pre_upgrade()?;
+			Self::set_temp_storage(Self::pool_snapshot()?, "pool_snapshot");
+			Ok(())
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade() -> Result<(), &'static str> {
+			Migrations::<T>::post_upgrade()?;
+			// Every pool has to be consistent after the upgrade, and k can't have dropped in it
+			let previous = Self::get_temp_storage::<PoolSnapshot<T>>("pool_snapshot").unwrap_or_default();
+			Self::do_try_state(&previous)
+		}
 	}
 
 	// HOOKS
@@ -133,15 +437,20 @@ pub mod pallet {
 			token_b: TokenIdOf<T>,
 			quantity_token_a: BalanceOf<T>,
 			quantity_token_b: BalanceOf<T>,
+			min_lp_out: BalanceOf<T>,
+			deadline: T::BlockNumber,
 		) -> DispatchResult {
 			
 			// Check if extrinsic was signed
 			let wallet = ensure_signed(origin)?;
 
+			// Check if extrinsic isn't included too late
+			Self::check_deadline(deadline)?;
+
 			// Check if tokens are not the same
 			ensure!(token_a != token_b, Error::<T>::IdenticalTokens);
 
-			// Check for other tokens than the allowed tokens to provide liquidity with (DOT, ETH, ADA, BTC)
+			// Check for other tokens than the allowed tokens to provide liquidity with
 			Self::check_if_valid_tokens(token_a, token_b)?;
 
 			// Check if wallet has enough funds
@@ -149,54 +458,110 @@ pub mod pallet {
 
 			// Create deposit struct where tokens are ordered, the amounts are ordered to the tokens.
 			// In addition, a tokenpair ID is created.
-			let deposit = Self::create_deposit(token_a, token_b, quantity_token_a, quantity_token_b);
+			let deposit = Self::create_deposit(token_a, token_b, quantity_token_a, quantity_token_b, min_lp_out);
 
 			// Check if pool already exists
-			if let Ok(pool) = AllPools::<T>::try_get(&deposit.tokenpair_id) {
-				// Deposit to existing pool
-				Self::deposit(deposit, wallet, pool, false)?;
-			} else {
-				// Create and deposit to new pool
-				let pool_id: T::AccountId = T::PalletId::get().into_sub_account_truncating(&deposit.tokenpair_id);
-				T::Balances::make_free_balance_be(&pool_id, 1_000u32.into());
-				AllPools::<T>::insert(&deposit.tokenpair_id, pool_id.clone());
-				Self::deposit(deposit, wallet, pool_id, true)?;
-			}
+			let pool = Pools::<T>::get(&deposit.tokenpair_id).ok_or(Error::<T>::PoolNotFound)?;
+			Self::deposit(deposit, wallet, pool)?;
+			Ok(())
+		}
+
+		/// Create the pool and its lp token for a pair of tokens. The creator pays the
+		/// `PoolCreationDeposit` and the existential deposit of the pool's account.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads(T::MaxLpTokenIdSkips::get().into()) + T::DbWeight::get().writes(1))]
+		pub fn create_pool(
+			origin: OriginFor<T>,
+			token_a: TokenIdOf<T>,
+			token_b: TokenIdOf<T>,
+		) -> DispatchResult {
+			// Check if extrinsic was signed
+			let creator = ensure_signed(origin)?;
+
+			// Check if tokens are not the same
+			ensure!(token_a != token_b, Error::<T>::IdenticalTokens);
+
+			// Check for other tokens than the allowed tokens to provide liquidity with
+			Self::check_if_valid_tokens(token_a, token_b)?;
+
+			// Sort the token pair: this is to prevent an (ETH, BTC) and a (BTC, ETH) pool
+			let mut tokenpair = vec![token_a, token_b];
+			tokenpair.sort();
+			let tokenpair_id = Self::create_token_pair_id(tokenpair[0], tokenpair[1]);
+			ensure!(!Pools::<T>::contains_key(&tokenpair_id), Error::<T>::PoolAlreadyExists);
+
+			// Reserve the deposit and fund the pool's account from the creator's wallet
+			let pool_id: T::AccountId = T::PalletId::get().into_sub_account_truncating(&tokenpair_id);
+			T::Balances::reserve(&creator, T::PoolCreationDeposit::get())?;
+			T::Balances::transfer(&creator, &pool_id, T::Balances::minimum_balance(), ExistenceRequirement::KeepAlive)?;
+
+			// Create a new LP token linked to the token pair ID
+			let lp_token_id = Self::next_lp_token_id()?;
+			T::Tokens::create(lp_token_id, pool_id.clone(), true, 1u32.into())?;
+			Pools::<T>::insert(&tokenpair_id, PoolInfo {
+				token_a: tokenpair[0],
+				token_b: tokenpair[1],
+				account: pool_id.clone(),
+				reserve_a: Zero::zero(),
+				reserve_b: Zero::zero(),
+				lp_token: lp_token_id,
+				lp_supply: Zero::zero(),
+				fee: T::SwapFee::get(),
+				created_at: frame_system::Pallet::<T>::block_number(),
+			});
+			AssetPools::<T>::mutate(tokenpair[0], |pools| *pools = pools.saturating_add(1));
+			AssetPools::<T>::mutate(tokenpair[1], |pools| *pools = pools.saturating_add(1));
+
+			Self::deposit_pool_event(&pool_id, Event::PoolCreated {
+				creator,
+				pool: pool_id.clone(),
+				token_a: tokenpair[0],
+				token_b: tokenpair[1],
+				lp_token: lp_token_id,
+			});
 			Ok(())
 		}
 
+		/// Burn `lp_amount` of a pool's lp tokens for the same share of both of its reserves.
 		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
 		pub fn withdraw_liquidity(
 			origin: OriginFor<T>,
 			token_a: TokenIdOf<T>,
 			token_b: TokenIdOf<T>,
 			lp_token: TokenIdOf<T>,
+			lp_amount: BalanceOf<T>,
+			min_token_a: BalanceOf<T>,
+			min_token_b: BalanceOf<T>,
+			deadline: T::BlockNumber,
 		) -> DispatchResult {
 
 			// Check if extrinsic was signed.
 			let wallet = ensure_signed(origin)?;
 
+			// Check if extrinsic isn't included too late.
+			Self::check_deadline(deadline)?;
+
 			// Check if tokens are not the same.
 			ensure!(token_a != token_b, Error::<T>::IdenticalTokens);
 
-			// Check for other tokens than the allowed tokens to provide liquidity with.
-			Self::check_if_valid_tokens(token_a, token_b)?;
+			// Check for other tokens than the allowed tokens to withdraw liquidity from.
+			Self::check_if_withdrawable_tokens(token_a, token_b)?;
 
 			// Check if user has lp tokens.
 			let lp_balance = T::Tokens::balance(lp_token, &wallet);
-			if lp_balance == 0u32.into() {
+			if lp_balance == 0u32.into() || lp_amount == 0u32.into() {
 				// If not throw error
 				ensure!(false, Error::<T>::NoTokens);
 			}
+			ensure!(lp_amount <= lp_balance, Error::<T>::NotEnoughFunds);
 
 			// Create withdrawal struct where tokens are ordered, the amounts are ordered to the tokens.
 			// In addition, a tokenpair ID is created.
-			let withdrawal = Self::create_withdrawal(token_a, token_b, lp_token);
+			let withdrawal = Self::create_withdrawal(token_a, token_b, lp_token, lp_amount, min_token_a, min_token_b);
 
 			// Check if pool already exists.
-			if let Ok(pool) = AllPools::<T>::try_get(&withdrawal.tokenpair_id) {
-				// Check if wallet has provided liquidity to this pool.
-				Self::check_if_liq_is_provided(&wallet, &pool)?;
+			if let Some(pool) = Pools::<T>::get(&withdrawal.tokenpair_id) {
+				// Any holder of the pool's lp token can withdraw.
+				ensure!(lp_token == pool.lp_token, Error::<T>::NoTokens);
 
 				// Deposit to existing pool.
 				Self::withdraw(withdrawal, wallet, pool)?;
@@ -212,10 +577,15 @@ pub mod pallet {
 			from_token: TokenIdOf<T>,
 			to_token: TokenIdOf<T>,
 			swap_amount: BalanceOf<T>,
+			min_amount_out: BalanceOf<T>,
+			deadline: T::BlockNumber,
 		) -> DispatchResult {
 			// Check if extrinsic was signed.
 			let wallet = ensure_signed(origin)?;
 
+			// Check if extrinsic isn't included too late.
+			Self::check_deadline(deadline)?;
+
 			// Check for other tokens than the allowed tokens to provide liquidity with.
 			Self::check_if_valid_tokens(from_token, to_token)?;
 
@@ -226,20 +596,180 @@ pub mod pallet {
 				ensure!(false, Error::<T>::NotEnoughFunds);
 			}
 
-			let mut tokenpair = vec![from_token, to_token];
-			tokenpair.sort();
+			// Check if pool already exists
+			let (tokenpair_id, pool) = Self::find_pool(from_token, to_token)?;
 
-			// Create token pair ID
-			let token_pair_id = Self::create_token_pair_id(tokenpair[0], tokenpair[1]);
+			// Make swap
+			Self::make_swap(wallet, tokenpair_id, pool, (from_token, to_token), swap_amount, min_amount_out)?;
+			Ok(())
+		}
+
+		/// Swap as little as possible, but no more than `max_amount_in`, of `from_token` for
+		/// exactly `amount_out` of `to_token`.
+		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+		pub fn swap_tokens_for_exact_tokens(
+			origin: OriginFor<T>,
+			from_token: TokenIdOf<T>,
+			to_token: TokenIdOf<T>,
+			amount_out: BalanceOf<T>,
+			max_amount_in: BalanceOf<T>,
+			deadline: T::BlockNumber,
+		) -> DispatchResult {
+			// Check if extrinsic was signed.
+			let wallet = ensure_signed(origin)?;
+
+			// Check if extrinsic isn't included too late.
+			Self::check_deadline(deadline)?;
+
+			// Check for other tokens than the allowed tokens to provide liquidity with.
+			Self::check_if_valid_tokens(from_token, to_token)?;
 
 			// Check if pool already exists
-			if let Ok(pool) = AllPools::<T>::try_get(&token_pair_id) {
-				// Make swap
-				Self::make_swap(wallet, pool, (from_token, to_token), swap_amount)?;	
-			} else {
-				// Pool does not exist yet
-				ensure!(false, Error::<T>::PoolNotFound);
+			let (tokenpair_id, pool) = Self::find_pool(from_token, to_token)?;
+
+			// Make swap
+			Self::make_swap_exact_out(wallet, tokenpair_id, pool, (from_token, to_token), amount_out, max_amount_in)?;
+			Ok(())
+		}
+
+		/// Swap `swap_amount` of the first token of `path` for the last token of `path`, hopping
+		/// through the pools of every consecutive pair of tokens. Only the final output is
+		/// checked against `min_amount_out`. Either all hops succeed or none.
+		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+		pub fn swap_along_path(
+			origin: OriginFor<T>,
+			path: BoundedVec<TokenIdOf<T>, T::MaxPathLength>,
+			swap_amount: BalanceOf<T>,
+			min_amount_out: BalanceOf<T>,
+			deadline: T::BlockNumber,
+		) -> DispatchResult {
+			// Check if extrinsic was signed.
+			let wallet = ensure_signed(origin)?;
+
+			// Check if extrinsic isn't included too late.
+			Self::check_deadline(deadline)?;
+
+			// Check if the path has at least one hop and never visits a token twice
+			ensure!(path.len() >= 2, Error::<T>::InvalidPath);
+			for (i, token) in path.iter().enumerate() {
+				ensure!(!path[i + 1..].contains(token), Error::<T>::InvalidPath);
+			}
+
+			// Check for other tokens than the allowed tokens to provide liquidity with.
+			for hop in path.windows(2) {
+				Self::check_if_valid_tokens(hop[0], hop[1])?;
 			}
+
+			// Check is user has token balance
+			ensure!(Self::check_balance(&wallet, path[0], swap_amount), Error::<T>::NotEnoughFunds);
+
+			// Make swaps
+			Self::make_swap_along_path(wallet, &path, swap_amount, min_amount_out)?;
+			Ok(())
+		}
+
+		/// Send the tokens in a pool's account on top of its reserves, e.g. sent there by
+		/// accident, to `to`.
+		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+		pub fn skim(
+			origin: OriginFor<T>,
+			token_a: TokenIdOf<T>,
+			token_b: TokenIdOf<T>,
+			to: T::AccountId,
+		) -> DispatchResult {
+			// Check if extrinsic was signed.
+			ensure_signed(origin)?;
+
+			// Check if pool already exists
+			let (_, pool) = Self::find_pool(token_a, token_b)?;
+
+			// Transfer the surplus of both tokens, the reserves stay in the pool
+			let (amount_a, amount_b) = Self::pool_surplus(&pool);
+			if !amount_a.is_zero() {
+				T::Tokens::transfer(pool.token_a, &pool.account, &to, amount_a, false)?;
+			}
+			if !amount_b.is_zero() {
+				T::Tokens::transfer(pool.token_b, &pool.account, &to, amount_b, false)?;
+			}
+
+			Self::deposit_pool_event(&pool.account, Event::Skimmed {
+				pool: pool.account.clone(),
+				to,
+				amount_a,
+				amount_b,
+			});
+			Ok(())
+		}
+
+		/// Make the tokens in a pool's account its reserves, e.g. to add tokens sent there by
+		/// accident to the liquidity.
+		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+		pub fn sync(
+			origin: OriginFor<T>,
+			token_a: TokenIdOf<T>,
+			token_b: TokenIdOf<T>,
+		) -> DispatchResult {
+			// Check if extrinsic was signed.
+			ensure_signed(origin)?;
+
+			// Check if pool already exists
+			let (tokenpair_id, mut pool) = Self::find_pool(token_a, token_b)?;
+
+			// Adopt the actual balances as reserves
+			pool.reserve_a = T::Tokens::balance(pool.token_a, &pool.account);
+			pool.reserve_b = T::Tokens::balance(pool.token_b, &pool.account);
+			Pools::<T>::insert(&tokenpair_id, &pool);
+
+			Self::deposit_pool_event(&pool.account, Event::Synced {
+				pool: pool.account.clone(),
+				reserve_a: pool.reserve_a,
+				reserve_b: pool.reserve_b,
+			});
+			Ok(())
+		}
+
+		/// Switch the protocol fee on (non-zero share of the liquidity fees) or off (zero).
+		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+		pub fn set_protocol_fee(origin: OriginFor<T>, share: Permill) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+			ProtocolFee::<T>::put(share);
+			Self::deposit_event(Event::ProtocolFeeSet { share });
+			Ok(())
+		}
+
+		/// Allow an asset to be used in the pools.
+		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+		pub fn add_asset(origin: OriginFor<T>, asset: TokenIdOf<T>) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+			ensure!(!AllowedAssets::<T>::contains_key(asset), Error::<T>::AssetAlreadyListed);
+			ensure!(asset < T::LpTokenIdStart::get(), Error::<T>::AssetIdReserved);
+			AllowedAssets::<T>::insert(asset, AssetStatus::Active);
+			Self::deposit_event(Event::AssetListed { asset });
+			Ok(())
+		}
+
+		/// Stop an asset from being used at all. Assets of existing pools can only be made
+		/// withdraw-only, so their liquidity can still be withdrawn.
+		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+		pub fn remove_asset(origin: OriginFor<T>, asset: TokenIdOf<T>) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+			ensure!(AllowedAssets::<T>::contains_key(asset), Error::<T>::AssetNotListed);
+
+			// Withdrawals need the asset listed, delisting it would lock the liquidity of its pools
+			ensure!(AssetPools::<T>::get(asset).is_zero(), Error::<T>::AssetInUse);
+			AllowedAssets::<T>::remove(asset);
+			Self::deposit_event(Event::AssetDelisted { asset });
+			Ok(())
+		}
+
+		/// Only allow liquidity with an asset to be withdrawn, or allow everything again.
+		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+		pub fn set_withdraw_only(origin: OriginFor<T>, asset: TokenIdOf<T>, withdraw_only: bool) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+			ensure!(AllowedAssets::<T>::contains_key(asset), Error::<T>::AssetNotListed);
+			let status = if withdraw_only { AssetStatus::WithdrawOnly } else { AssetStatus::Active };
+			AllowedAssets::<T>::insert(asset, status);
+			Self::deposit_event(Event::AssetWithdrawOnlySet { asset, withdraw_only });
 			Ok(())
 		}
 	}
@@ -249,26 +779,170 @@ pub mod pallet {
 		where TokenIdOf<T>: Ord + PartialOrd + AtLeast32Bit + Copy {
 		fn make_swap(
 			wallet: T::AccountId,
-			pool_id: T::AccountId,
+			tokenpair_id: [u8; 16],
+			pool: PoolInfoOf<T>,
 			token_swap: (TokenIdOf<T>, TokenIdOf<T>),
 			swap_amount: BalanceOf<T>,
+			min_amount_out: BalanceOf<T>,
 		) -> DispatchResult {
+			let (swap_reward, swap_fee) = Self::price_swap(&pool, token_swap, swap_amount)?;
 
-			let pool_balance_a = T::Tokens::balance(token_swap.0, &wallet);
-			let pool_balance_b = T::Tokens::balance(token_swap.1, &wallet);
-			
-			let mut swap_reward = 0u32.into();
-			// Calculate swap
-			match DexPricer::swap(swap_amount, (pool_balance_a, pool_balance_b)) {
-				Some(x) => swap_reward = x,
-				None => ensure!(false, Error::<T>::MathProblem),
+			// Check if the price didn't move too much
+			ensure!(swap_reward >= min_amount_out, Error::<T>::SlippageExceeded);
+
+			Self::execute_swap(wallet, tokenpair_id, pool, token_swap, swap_amount, swap_reward, swap_fee)
+		}
+
+		fn make_swap_along_path(
+			wallet: T::AccountId,
+			path: &[TokenIdOf<T>],
+			swap_amount: BalanceOf<T>,
+			min_amount_out: BalanceOf<T>,
+		) -> DispatchResult {
+			// Tokens of every hop come from the wallet or the pool of the previous hop
+			let mut sender = wallet.clone();
+			let mut amount_in = swap_amount;
+			for hop in path.windows(2) {
+				let (tokenpair_id, mut pool) = Self::find_pool(hop[0], hop[1])?;
+
+				// Price the hop before its input arrives in the pool
+				let (amount_out, fee) = Self::price_swap(&pool, (hop[0], hop[1]), amount_in)?;
+				T::Tokens::transfer(hop[0], &sender, &pool.account, amount_in, true)?;
+
+				// The output leaves the pool with the next hop
+				Self::update_swap_reserves(&mut pool, (hop[0], hop[1]), amount_in, amount_out)?;
+				Pools::<T>::insert(&tokenpair_id, &pool);
+
+				let (reserve_in, reserve_out) = Self::swap_reserves(&pool, hop[0]);
+				Self::deposit_pool_event(&pool.account, Event::SwapOccured {
+					who: wallet.clone(),
+					pool: pool.account.clone(),
+					token_in: hop[0],
+					token_out: hop[1],
+					amount_in,
+					amount_out,
+					fee,
+					reserve_in,
+					reserve_out,
+				});
+				sender = pool.account;
+				amount_in = amount_out;
 			}
-			
+
+			// Only the final output is checked, the hops in between can have any price
+			ensure!(amount_in >= min_amount_out, Error::<T>::SlippageExceeded);
+
+			// Transfer tokens from the last pool's wallet to user's wallet
+			let to_token = path[path.len() - 1];
+			T::Tokens::transfer(to_token, &sender, &wallet, amount_in, true)?;
+			Ok(())
+		}
+
+		fn swap_reserves(pool: &PoolInfoOf<T>, from_token: TokenIdOf<T>) -> (BalanceOf<T>, BalanceOf<T>) {
+			// Reserves of the token going in and the token going out
+			if from_token == pool.token_a {
+				(pool.reserve_a, pool.reserve_b)
+			} else {
+				(pool.reserve_b, pool.reserve_a)
+			}
+		}
+
+		fn pool_surplus(pool: &PoolInfoOf<T>) -> (BalanceOf<T>, BalanceOf<T>) {
+			// Tokens in the pool's account that aren't part of the reserves
+			let balance_a = T::Tokens::balance(pool.token_a, &pool.account);
+			let balance_b = T::Tokens::balance(pool.token_b, &pool.account);
+			(balance_a.saturating_sub(pool.reserve_a), balance_b.saturating_sub(pool.reserve_b))
+		}
+
+		fn update_swap_reserves(
+			pool: &mut PoolInfoOf<T>,
+			token_swap: (TokenIdOf<T>, TokenIdOf<T>),
+			amount_in: BalanceOf<T>,
+			amount_out: BalanceOf<T>,
+		) -> DispatchResult {
+			let (reserve_in, reserve_out) = Self::swap_reserves(pool, token_swap.0);
+			let reserve_in = reserve_in.checked_add(&amount_in).ok_or(Error::<T>::Overflow)?;
+			let reserve_out = reserve_out.checked_sub(&amount_out).ok_or(Error::<T>::ReserveDrained)?;
+			if token_swap.0 == pool.token_a {
+				pool.reserve_a = reserve_in;
+				pool.reserve_b = reserve_out;
+			} else {
+				pool.reserve_b = reserve_in;
+				pool.reserve_a = reserve_out;
+			}
+			Ok(())
+		}
+
+		fn price_swap(
+			pool: &PoolInfoOf<T>,
+			token_swap: (TokenIdOf<T>, TokenIdOf<T>),
+			swap_amount: BalanceOf<T>,
+		) -> Result<(BalanceOf<T>, BalanceOf<T>), DispatchError> {
+
+			// Get the pool's reserves of the token going in and the token going out
+			let (reserve_in, reserve_out) = Self::swap_reserves(pool, token_swap.0);
+
+			// Calculate swap along the constant product curve, the fee is left in the pool
+			let (swap_reward, swap_fee) = DexPricer::swap(swap_amount, (reserve_in, reserve_out), pool.fee)
+				.map_err(Error::<T>::from)?;
+
+			// The pool always needs to keep some of both tokens
+			ensure!(swap_reward < reserve_out, Error::<T>::ReserveDrained);
+			Ok((swap_reward, swap_fee))
+		}
+
+		fn make_swap_exact_out(
+			wallet: T::AccountId,
+			tokenpair_id: [u8; 16],
+			pool: PoolInfoOf<T>,
+			token_swap: (TokenIdOf<T>, TokenIdOf<T>),
+			amount_out: BalanceOf<T>,
+			max_amount_in: BalanceOf<T>,
+		) -> DispatchResult {
+
+			let (swap_amount, swap_fee) = Self::price_swap_exact_out(&pool, token_swap, amount_out)?;
+
+			// Check if the price didn't move too much
+			ensure!(swap_amount <= max_amount_in, Error::<T>::SlippageExceeded);
+
+			// Check is user has token balance
+			ensure!(Self::check_balance(&wallet, token_swap.0, swap_amount), Error::<T>::NotEnoughFunds);
+
+			Self::execute_swap(wallet, tokenpair_id, pool, token_swap, swap_amount, amount_out, swap_fee)
+		}
+
+		fn price_swap_exact_out(
+			pool: &PoolInfoOf<T>,
+			token_swap: (TokenIdOf<T>, TokenIdOf<T>),
+			amount_out: BalanceOf<T>,
+		) -> Result<(BalanceOf<T>, BalanceOf<T>), DispatchError> {
+
+			// Get the pool's reserves of the token going in and the token going out
+			let (reserve_in, reserve_out) = Self::swap_reserves(pool, token_swap.0);
+
+			// The pool always needs to keep some of both tokens
+			ensure!(amount_out < reserve_out, Error::<T>::ReserveDrained);
+
+			// Calculate the input needed along the constant product curve, including the fee
+			let (swap_amount, swap_fee) = DexPricer::swap_exact_out(amount_out, (reserve_in, reserve_out), pool.fee)
+				.map_err(Error::<T>::from)?;
+			Ok((swap_amount, swap_fee))
+		}
+
+		fn execute_swap(
+			wallet: T::AccountId,
+			tokenpair_id: [u8; 16],
+			mut pool: PoolInfoOf<T>,
+			token_swap: (TokenIdOf<T>, TokenIdOf<T>),
+			swap_amount: BalanceOf<T>,
+			swap_reward: BalanceOf<T>,
+			swap_fee: BalanceOf<T>,
+		) -> DispatchResult {
 			// Transfer tokens from user's wallet to pool's wallet
 			T::Tokens::transfer(
 				token_swap.0, 
 				&wallet,
-				&pool_id,
+				&pool.account,
 				swap_amount,
 				true
 			)?;
@@ -276,16 +950,28 @@ pub mod pallet {
 			// Transfer tokens from pool's wallet to user's wallet
 			T::Tokens::transfer(
 				token_swap.1, 
-				&pool_id,
+				&pool.account,
 				&wallet,
 				swap_reward,
 				true
 			)?;
 
+			// Keep track of the pool's reserves, the fee stays in the pool
+			Self::update_swap_reserves(&mut pool, token_swap, swap_amount, swap_reward)?;
+			Pools::<T>::insert(&tokenpair_id, &pool);
+
 			// Swap succesful
-			Self::deposit_event(Event::SwapOccured {
-				from: wallet,
-				to: pool_id,
+			let (reserve_in, reserve_out) = Self::swap_reserves(&pool, token_swap.0);
+			Self::deposit_pool_event(&pool.account, Event::SwapOccured {
+				who: wallet,
+				pool: pool.account.clone(),
+				token_in: token_swap.0,
+				token_out: token_swap.1,
+				amount_in: swap_amount,
+				amount_out: swap_reward,
+				fee: swap_fee,
+				reserve_in,
+				reserve_out,
 			});
 			Ok(())
 		}
@@ -325,15 +1011,21 @@ pub mod pallet {
 		}
 
 		fn check_if_valid_tokens(token_a: TokenIdOf<T>, token_b: TokenIdOf<T>) -> DispatchResult {
-			// Create DOT, ETH, ADA, BTC
-			let dot: TokenIdOf<T> = 1u32.into();
-			let eth: TokenIdOf<T> = 2u32.into();
-			let ada: TokenIdOf<T> = 3u32.into();
-			let btc: TokenIdOf<T> = 4u32.into();
-
-			// Check for other tokens than DOT, ETH, ADA, BTC
-			ensure!(token_a == dot || token_a == eth || token_a == ada || token_a == btc, Error::<T>::InvalidToken);	
-			ensure!(token_b == dot || token_b == eth || token_b == ada || token_b == btc, Error::<T>::InvalidToken);	
+			// Check for tokens that aren't listed or can only be withdrawn
+			for token in [token_a, token_b] {
+				match AllowedAssets::<T>::get(token) {
+					Some(AssetStatus::Active) => (),
+					Some(AssetStatus::WithdrawOnly) => ensure!(false, Error::<T>::TokenWithdrawOnly),
+					None => ensure!(false, Error::<T>::InvalidToken),
+				}
+			}
+			Ok(())
+		}
+
+		fn check_if_withdrawable_tokens(token_a: TokenIdOf<T>, token_b: TokenIdOf<T>) -> DispatchResult {
+			// Check for tokens that aren't listed, withdraw-only tokens are fine
+			ensure!(AllowedAssets::<T>::contains_key(token_a), Error::<T>::InvalidToken);
+			ensure!(AllowedAssets::<T>::contains_key(token_b), Error::<T>::InvalidToken);
 			Ok(())
 		}
 
@@ -342,6 +1034,7 @@ pub mod pallet {
 			token_b: TokenIdOf<T>,
 			unsorted_quant_token_a: BalanceOf<T>,
 			unsorted_quant_token_b: BalanceOf<T>,
+			min_lp_out: BalanceOf<T>,
 		) -> Deposit<T> {
 			// Sort the token pair: this is to prevent an (ETH, BTC) and a (BTC, ETH) pool
 			let mut tokenpair = vec![token_a, token_b];
@@ -363,10 +1056,40 @@ pub mod pallet {
 				tokenpair_id,
 				quantity_token_a,
 				quantity_token_b,
+				min_lp_out,
 			}
 		}
 
-		fn create_token_pair_id(token_a: TokenIdOf<T>, token_b: TokenIdOf<T>) -> [u8; 16] {
+		fn next_lp_token_id() -> Result<TokenIdOf<T>, DispatchError> {
+			// Lp tokens get consecutive IDs from `LpTokenIdStart` on, which can't be listed
+			let mut lp_token_id = NextLpTokenId::<T>::get().unwrap_or_else(T::LpTokenIdStart::get);
+
+			// Anyone can create assets, skip the IDs that are taken already (existing assets have
+			// a minimum balance), up to the number of reads the weight pays for
+			let mut skipped = 0u32;
+			while !T::Tokens::minimum_balance(lp_token_id).is_zero() {
+				ensure!(skipped < T::MaxLpTokenIdSkips::get(), Error::<T>::LpTokenIdsExhausted);
+				skipped += 1;
+				lp_token_id = lp_token_id.checked_add(&One::one()).ok_or(Error::<T>::LpTokenIdsExhausted)?;
+			}
+			let next = lp_token_id.checked_add(&One::one()).ok_or(Error::<T>::LpTokenIdsExhausted)?;
+			NextLpTokenId::<T>::put(next);
+			Ok(lp_token_id)
+		}
+
+		fn find_pool(token_a: TokenIdOf<T>, token_b: TokenIdOf<T>) -> Result<([u8; 16], PoolInfoOf<T>), DispatchError> {
+			let mut tokenpair = vec![token_a, token_b];
+			tokenpair.sort();
+
+			// Create token pair ID
+			let token_pair_id = Self::create_token_pair_id(tokenpair[0], tokenpair[1]);
+
+			// Pool might not exist yet
+			let pool = Pools::<T>::get(&token_pair_id).ok_or(Error::<T>::PoolNotFound)?;
+			Ok((token_pair_id, pool))
+		}
+
+		pub(crate) fn create_token_pair_id(token_a: TokenIdOf<T>, token_b: TokenIdOf<T>) -> [u8; 16] {
 			// Use blake2 to create a deterministic token pair ID
 			// First hash both token ID's (u32)
 			let mut hash1 = token_a.blake2_128_concat();
@@ -384,17 +1107,36 @@ pub mod pallet {
 			token_a: TokenIdOf<T>,
 			token_b: TokenIdOf<T>,
 			lp_token: TokenIdOf<T>,
+			lp_amount: BalanceOf<T>,
+			unsorted_min_token_a: BalanceOf<T>,
+			unsorted_min_token_b: BalanceOf<T>,
 		) -> Withdrawal<T> {
 			let mut tokenpair = vec![token_a, token_b];
+			let cloned_tokenpair = tokenpair.clone();
 			tokenpair.sort();
 			let tokenpair_id = Self::create_token_pair_id(tokenpair[0], tokenpair[1]);
+
+			// If tokenpair is sorted differently, the minimum amounts need to swap
+			let (min_token_a, min_token_b) = if tokenpair != cloned_tokenpair {
+				(unsorted_min_token_b, unsorted_min_token_a)
+			} else {
+				(unsorted_min_token_a, unsorted_min_token_b)
+			};
 			Withdrawal {
 				tokenpair,
 				tokenpair_id,
 				lp_token,
+				lp_amount,
+				min_token_a,
+				min_token_b,
 			}
 		}
 
+		fn check_deadline(deadline: T::BlockNumber) -> DispatchResult {
+			ensure!(frame_system::Pallet::<T>::block_number() <= deadline, Error::<T>::DeadlinePassed);
+			Ok(())
+		}
+
 		fn check_swap_token_a(from_token: TokenIdOf<T>, to_token: TokenIdOf<T>) -> (TokenIdOf<T>, TokenIdOf<T>, bool) {
 			// Check for from_token in sorted manner
 			let mut tokenpair = vec![from_token, to_token];
@@ -406,35 +1148,24 @@ pub mod pallet {
 			}
 		}
 
-		fn check_if_liq_is_provided(wallet: &T::AccountId, pool_id: &T::AccountId) -> DispatchResult {
-			let liq_providers = LiquidityProviders::<T>::get(pool_id);
-			if let Some(_) = liq_providers.iter().position(|id| id == wallet) {
-				return Ok(());
-			} 
-			ensure!(false, Error::<T>::NoLiquidityProvided);
-			Ok(())
-		}
-
 		fn deposit(
-			deposit: Deposit<T>,
+			mut deposit: Deposit<T>,
 			wallet: T::AccountId,
-			pool_id: T::AccountId,
-			new_pool_bool: bool,
+			mut pool: PoolInfoOf<T>,
 		) -> DispatchResult {
-			// Specified whether deposit is made to a new pool or already existing
+			// The first deposit into a pool, when no lp tokens are given out, sets its price
 			// (Matters for the calculation)
-			if new_pool_bool {
-				Self::deposit_to_new_pool(&deposit, &wallet, pool_id.clone())?;	
+			let lp_minted = if pool.lp_supply.is_zero() {
+				Self::deposit_to_new_pool(&deposit, &wallet, &mut pool)?
 			} else {
-				// Self::deposit_calculations(deposit, &wallet, pool)
-				Self::deposit_to_existing_pool(&deposit, &wallet, &pool_id)?;
-			}
+				Self::deposit_to_existing_pool(&mut deposit, &wallet, &mut pool)?
+			};
 
 			// Transfer tokens from user's wallet to pool's wallet
 			T::Tokens::transfer(
 				deposit.tokenpair[0], 
 				&wallet,
-				&pool_id,
+				&pool.account,
 				deposit.quantity_token_a,
 				true
 			)?;
@@ -443,161 +1174,158 @@ pub mod pallet {
 			T::Tokens::transfer(
 				deposit.tokenpair[1], 
 				&wallet,
-				&pool_id,
+				&pool.account,
 				deposit.quantity_token_b,
 				true
 			)?;
 
+			// Keep track of the pool's reserves
+			pool.reserve_a = pool.reserve_a.checked_add(&deposit.quantity_token_a).ok_or(Error::<T>::Overflow)?;
+			pool.reserve_b = pool.reserve_b.checked_add(&deposit.quantity_token_b).ok_or(Error::<T>::Overflow)?;
+
+			// Remember k for the next protocol fee
+			Self::update_root_k(&pool)?;
+			Pools::<T>::insert(&deposit.tokenpair_id, &pool);
+
 			// Deposit succesful
-			Self::deposit_event(Event::LiquidityDeposited {
-				from: wallet,
-				to: pool_id,
+			Self::deposit_pool_event(&pool.account, Event::LiquidityDeposited {
+				who: wallet,
+				pool: pool.account.clone(),
 				token_a: deposit.tokenpair[0],
 				token_b: deposit.tokenpair[1],
+				amount_a: deposit.quantity_token_a,
+				amount_b: deposit.quantity_token_b,
+				lp_token: pool.lp_token,
+				lp_minted,
+				reserve_a: pool.reserve_a,
+				reserve_b: pool.reserve_b,
 			});
 			Ok(())
 		}
 
-		fn deposit_to_new_pool(deposit: &Deposit<T>, wallet: &T::AccountId, pool_id: T::AccountId) -> DispatchResult {
-			let mut lp_reward = 0u32.into();
-			// Calculate lp reward
-			match DexPricer::new_pool_function(deposit.quantity_token_a, deposit.quantity_token_b) {
-				Some(x) => lp_reward = x,
-				None => ensure!(false, Error::<T>::MathProblem),
-			}
-
-			// A funny but not perfect way of creating a save lp token id by decoding the token pair id
-			let maybe_value = u32::decode(&mut &*deposit.tokenpair_id.to_vec());
-			if maybe_value.is_err() {
-				return Err(sp_runtime::DispatchError::BadOrigin);
+		/// Returns the lp tokens minted into the wallet.
+		fn deposit_to_new_pool(
+			deposit: &Deposit<T>,
+			wallet: &T::AccountId,
+			pool: &mut PoolInfoOf<T>,
+		) -> Result<BalanceOf<T>, DispatchError> {
+			// Calculate lp tokens minted
+			let lp_minted = DexPricer::new_pool_function(deposit.quantity_token_a, deposit.quantity_token_b)
+				.map_err(Error::<T>::from)?;
+
+			// The minimum liquidity is locked forever, the rest is the user's reward
+			let minimum_liquidity = T::MinimumLiquidity::get();
+			let lp_reward = lp_minted.saturating_sub(minimum_liquidity);
+			ensure!(!lp_reward.is_zero(), Error::<T>::InsufficientLiquidityMinted);
+
+			// Check if the user gets enough lp tokens
+			ensure!(lp_reward >= deposit.min_lp_out, Error::<T>::SlippageExceeded);
+
+			// Mint token reward amount into user's wallet and the locked lp tokens into the pool's wallet,
+			// and keep track of the amount of lp tokens minted
+			T::Tokens::mint_into(pool.lp_token, wallet, lp_reward)?;
+			if !minimum_liquidity.is_zero() {
+				T::Tokens::mint_into(pool.lp_token, &pool.account, minimum_liquidity)?;
 			}
-			let value = maybe_value.expect("value checked to be 'Some'; eqd");
-			let lp_token_id: TokenIdOf<T> = value.into();
-
-			// Create a new LP token linked to the token pair ID
-			T::Tokens::create(lp_token_id, pool_id.clone(), true, 1u32.into())?;
-
-			// Mint token reward amount into user's wallet and into the pool's wallet
-			// The latter is for keeping track of the amount of lp tokens minted
-			T::Tokens::mint_into(lp_token_id, wallet, lp_reward)?;
-			T::Tokens::mint_into(lp_token_id, &pool_id, lp_reward)?;
-
-			// Making sure that a pool has no more than 4 liquidity providers (Only 4 users exist)
-			LiquidityProviders::<T>::try_append(&pool_id, wallet).map_err(|_| Error::<T>::LiqProvidersOverflow)?;
-			Ok(())
+			pool.lp_supply = lp_minted;
+			Ok(lp_reward)
 		}
 
-		fn deposit_to_existing_pool(deposit: &Deposit<T>, wallet: &T::AccountId, pool_id: &T::AccountId) -> DispatchResult {
-			// Function to check for no more than 4 liq providers.
-			// Shouldn't be possible because there are only 4 users.
-			// Wasn't really sure whether I needed it.
-			Self::check_liq_providers_overflow(wallet, pool_id)?;
-
-			// Obtain token a & token b amount from the pool
-			let pool_amount_a = T::Tokens::balance(deposit.tokenpair[0], &pool_id);
-
-			// Again, a funny but deterministic way of obtaining the lp token ID
-			let maybe_value = u32::decode(&mut &*deposit.tokenpair_id.to_vec());
-			if maybe_value.is_err() {
-				return Err(sp_runtime::DispatchError::BadOrigin);
-			}
-			let value = maybe_value.expect("value checked to be 'Some'; eqd");
-			let lp_token_id: TokenIdOf<T> = value.into();
-
-			// Get amount of lp tokens given out already by the pool
-			let lp_minted = T::Tokens::balance(lp_token_id, &pool_id);
+		/// Returns the lp tokens minted into the wallet.
+		fn deposit_to_existing_pool(
+			deposit: &mut Deposit<T>,
+			wallet: &T::AccountId,
+			pool: &mut PoolInfoOf<T>,
+		) -> Result<BalanceOf<T>, DispatchError> {
+			// Only take what matches the pool's ratio, the excess stays in the wallet
+			let (quantity_token_a, quantity_token_b) = DexPricer::optimal_deposit(
+				(deposit.quantity_token_a, deposit.quantity_token_b),
+				(pool.reserve_a, pool.reserve_b),
+			)
+			.map_err(Error::<T>::from)?;
+			deposit.quantity_token_a = quantity_token_a;
+			deposit.quantity_token_b = quantity_token_b;
+
+			// The protocol's share of the fees is minted before the deposit changes the pool
+			Self::mint_protocol_fee(pool)?;
 
-			let mut lp_reward = 0u32.into();
 			// Calculate lp reward
-			match DexPricer::existing_pool_function(deposit.quantity_token_a, pool_amount_a, lp_minted) {
-				Some(x) => lp_reward = x,
-				None => ensure!(false, Error::<T>::MathProblem),
-			}
-
-			// Give wallet lp reward as well as updating the total amount of lp tokens given out (by minting the token)
-			T::Tokens::mint_into(lp_token_id, &wallet, lp_reward)?;
-			T::Tokens::mint_into(lp_token_id, &pool_id, lp_reward)?;
-			Ok(())
-		}
-
-		fn check_liq_providers_overflow(wallet: &T::AccountId, pool_id: &T::AccountId) -> DispatchResult {
-			// Get all liq providers' wallets addresses
-			let liq_providers = LiquidityProviders::<T>::get(pool_id);
-
-			// See if wallet already provided liquidity to this pool
-			if let Some(_) = liq_providers.iter().position(|id| id == wallet) {
-				// If so, continue
-				();
-			} else {
-				// If not, add wallet to the list 
-				LiquidityProviders::<T>::try_append(pool_id, wallet).map_err(|_| Error::<T>::LiqProvidersOverflow)?;
-			}
-			Ok(())
+			let lp_reward = DexPricer::existing_pool_function(
+				(deposit.quantity_token_a, deposit.quantity_token_b),
+				(pool.reserve_a, pool.reserve_b),
+				pool.lp_supply,
+			)
+			.map_err(Error::<T>::from)?;
+
+			// Check if the user gets any and enough lp tokens
+			ensure!(!lp_reward.is_zero(), Error::<T>::InsufficientLiquidityMinted);
+			ensure!(lp_reward >= deposit.min_lp_out, Error::<T>::SlippageExceeded);
+
+			// Give wallet lp reward as well as updating the total amount of lp tokens given out
+			T::Tokens::mint_into(pool.lp_token, &wallet, lp_reward)?;
+			pool.lp_supply = pool.lp_supply.checked_add(&lp_reward).ok_or(Error::<T>::Overflow)?;
+			Ok(lp_reward)
 		}
 
 		fn withdraw(
 			withdrawal: Withdrawal<T>,
 			wallet: T::AccountId,
-			pool_id: T::AccountId,
+			mut pool: PoolInfoOf<T>,
 		) -> DispatchResult {
+			// The protocol's share of the fees is minted before the withdrawal changes the pool
+			Self::mint_protocol_fee(&mut pool)?;
+
 			// Get total amount of liquidity provided for token a and token b
-			let quantity_token_a = T::Tokens::balance(withdrawal.tokenpair[0], &pool_id);
-			let quantity_token_b = T::Tokens::balance(withdrawal.tokenpair[1], &pool_id);
+			let quantity_token_a = pool.reserve_a;
+			let quantity_token_b = pool.reserve_b;
 
-			// Again, a funny but deterministic way of obtaining the lp token ID
-			let maybe_value = u32::decode(&mut &*withdrawal.tokenpair_id.to_vec());
-			if maybe_value.is_err() {
-				return Err(sp_runtime::DispatchError::BadOrigin);
-			}
-			let value = maybe_value.expect("value checked to be 'Some'; eqd");
-			let lp_token_id: TokenIdOf<T> = value.into();
+			// Amount of lp tokens to burn
+			let lp_tokens = withdrawal.lp_amount;
 
-			// Get amount of lp tokens in wallet
-			let lp_tokens = T::Tokens::balance(lp_token_id, &wallet);
+			// Get amount of lp tokens given out by the pool, the minimum liquidity always stays
+			let lp_minted = pool.lp_supply;
+			let lp_remaining = lp_minted.checked_sub(&lp_tokens).ok_or(Error::<T>::LiquidityBelowMinimum)?;
+			ensure!(lp_remaining >= T::MinimumLiquidity::get(), Error::<T>::LiquidityBelowMinimum);
 
-			// Get amount of lp tokens given out by the pool
-			let lp_minted = T::Tokens::balance(lp_token_id, &pool_id);
+			// Calculating the liquidity rewards, pro rata to the lp tokens burned
+			let liq_reward_a = DexPricer::liquidity_reward(lp_tokens, lp_minted, quantity_token_a)
+				.map_err(Error::<T>::from)?;
+			let liq_reward_b = DexPricer::liquidity_reward(lp_tokens, lp_minted, quantity_token_b)
+				.map_err(Error::<T>::from)?;
 
-			// If more lp tokens than lp minted my calculations were not precise enough and total pool is for wallet.
-			// Would have done this differently if I had tested the math more properly
-			if lp_tokens >= lp_minted {
-				// Update tokens given out by pool and burn tokens from wallet
-				T::Tokens::burn_from(lp_token_id, &pool_id, lp_tokens)?;
-				T::Tokens::burn_from(lp_token_id, &wallet, lp_tokens)?;
+			// Check if the pool didn't change too much
+			ensure!(
+				liq_reward_a >= withdrawal.min_token_a && liq_reward_b >= withdrawal.min_token_b,
+				Error::<T>::SlippageExceeded
+			);
 
-				// Withdrawal succesful
-				Self::withdrawal_event(&withdrawal, &wallet, &pool_id, quantity_token_a, quantity_token_b)?;
-			} else {
-				
-				// Setting reward variables so I have them in this scope
-				let mut liq_reward_a = 0u32.into();
-				let mut liq_reward_b = 0u32.into();
-				
-				// Calculating the liquidity rewards 
-				// Calculate lp reward a
-				match DexPricer::liquidity_reward(lp_tokens, lp_minted, quantity_token_a) {
-					Some(x) => liq_reward_a = x,
-					None => ensure!(false, Error::<T>::MathProblem),
-				}
-				// Calculate lp reward b
-				match DexPricer::liquidity_reward(lp_tokens, lp_minted, quantity_token_b) {
-					Some(x) => liq_reward_b = x,
-					None => ensure!(false, Error::<T>::MathProblem),
-				}
-				
-				// Update tokens given out by pool and burn tokens from wallet
-				T::Tokens::burn_from(lp_token_id, &pool_id, lp_tokens)?;
-				T::Tokens::burn_from(lp_token_id, &wallet, lp_tokens)?;
+			// Burn tokens from wallet and update tokens given out by pool
+			T::Tokens::burn_from(pool.lp_token, &wallet, lp_tokens)?;
+			pool.lp_supply = lp_remaining;
 
-				// Make transfers
-				Self::withdrawal_event(&withdrawal, &wallet, &pool_id, liq_reward_a, liq_reward_b)?;
-			}
+			// Make transfers
+			Self::withdrawal_event(&withdrawal, &wallet, &pool.account, liq_reward_a, liq_reward_b)?;
+
+			// Keep track of the pool's reserves
+			pool.reserve_a = quantity_token_a.checked_sub(&liq_reward_a).ok_or(Error::<T>::ReserveDrained)?;
+			pool.reserve_b = quantity_token_b.checked_sub(&liq_reward_b).ok_or(Error::<T>::ReserveDrained)?;
+
+			// Remember k for the next protocol fee
+			Self::update_root_k(&pool)?;
+			Pools::<T>::insert(&withdrawal.tokenpair_id, &pool);
 
 			// Withdrawal succesful
-			Self::deposit_event(Event::LiquidityWithdrawn {
-				from: wallet,
-				to: pool_id,
-				lp_token: lp_token_id,
+			Self::deposit_pool_event(&pool.account, Event::LiquidityWithdrawn {
+				who: wallet,
+				pool: pool.account.clone(),
+				token_a: withdrawal.tokenpair[0],
+				token_b: withdrawal.tokenpair[1],
+				amount_a: liq_reward_a,
+				amount_b: liq_reward_b,
+				lp_token: pool.lp_token,
+				lp_burned: lp_tokens,
+				reserve_a: pool.reserve_a,
+				reserve_b: pool.reserve_b,
 			});
 			Ok(())
 		}
@@ -629,5 +1357,248 @@ pub mod pallet {
 			)?;
 			Ok(())
 		}
+
+		fn pending_protocol_fee(pool: &PoolInfoOf<T>) -> Result<BalanceOf<T>, DispatchError> {
+			// Nothing to mint if the fee is switched off or k wasn't recorded yet
+			let share = ProtocolFee::<T>::get();
+			let root_k_last = LastRootK::<T>::get(&pool.account);
+			if share.is_zero() || root_k_last.is_zero() {
+				return Ok(Zero::zero());
+			}
+
+			// Growth of k since the last deposit or withdrawal comes from the swap fees
+			let root_k = DexPricer::new_pool_function(pool.reserve_a, pool.reserve_b).map_err(Error::<T>::from)?;
+			let protocol_lp = DexPricer::protocol_fee(pool.lp_supply, root_k, root_k_last, share)
+				.map_err(Error::<T>::from)?;
+			Ok(protocol_lp)
+		}
+
+		fn mint_protocol_fee(pool: &mut PoolInfoOf<T>) -> DispatchResult {
+			let protocol_lp = Self::pending_protocol_fee(pool)?;
+			if !protocol_lp.is_zero() {
+				// Mint into the receiver and keep track of the lp tokens minted
+				let receiver = T::ProtocolFeeReceiver::get();
+				T::Tokens::mint_into(pool.lp_token, &receiver, protocol_lp)?;
+				pool.lp_supply = pool.lp_supply.checked_add(&protocol_lp).ok_or(Error::<T>::Overflow)?;
+
+				Self::deposit_pool_event(&pool.account, Event::ProtocolFeeMinted {
+					pool: pool.account.clone(),
+					lp_token: pool.lp_token,
+					amount: protocol_lp,
+				});
+			}
+			Ok(())
+		}
+
+		/// Topic under which the events of a pool are indexed.
+		pub fn pool_topic(pool: &T::AccountId) -> T::Hash {
+			<T::Hashing as sp_runtime::traits::Hash>::hash_of(pool)
+		}
+
+		fn deposit_pool_event(pool: &T::AccountId, event: Event<T>) {
+			// Index the event by its pool, so clients can follow a single pool
+			let event: <T as Config>::Event = event.into();
+			frame_system::Pallet::<T>::deposit_event_indexed(&[Self::pool_topic(pool)], event.into());
+		}
+
+		fn update_root_k(pool: &PoolInfoOf<T>) -> DispatchResult {
+			if ProtocolFee::<T>::get().is_zero() {
+				LastRootK::<T>::remove(&pool.account);
+			} else {
+				let root_k = DexPricer::new_pool_function(pool.reserve_a, pool.reserve_b).map_err(Error::<T>::from)?;
+				LastRootK::<T>::insert(&pool.account, root_k);
+			}
+			Ok(())
+		}
+	}
+
+	// QUERIES
+	/// Pricing of trades without making them, for the `DexApi` runtime API. The amounts are what
+	/// the extrinsics would give at the current state, before slippage and balance checks. Like
+	/// the extrinsics, swaps and deposits need listed tokens, withdrawals also take withdraw-only
+	/// ones.
+	impl<T: Config> Pallet<T>
+		where TokenIdOf<T>: Ord + PartialOrd + AtLeast32Bit + Copy {
+		/// Reserves of the pool of `token_a` and `token_b`, in that order.
+		pub fn get_reserves(
+			token_a: TokenIdOf<T>,
+			token_b: TokenIdOf<T>,
+		) -> Result<(BalanceOf<T>, BalanceOf<T>), DispatchError> {
+			let (_, pool) = Self::find_pool(token_a, token_b)?;
+			if token_a == pool.token_a {
+				Ok((pool.reserve_a, pool.reserve_b))
+			} else {
+				Ok((pool.reserve_b, pool.reserve_a))
+			}
+		}
+
+		/// Tokens `swap` gives for `amount_in` of `token_in`.
+		pub fn quote_exact_in(
+			token_in: TokenIdOf<T>,
+			token_out: TokenIdOf<T>,
+			amount_in: BalanceOf<T>,
+		) -> Result<BalanceOf<T>, DispatchError> {
+			Self::check_if_valid_tokens(token_in, token_out)?;
+			let (_, pool) = Self::find_pool(token_in, token_out)?;
+			let (amount_out, _) = Self::price_swap(&pool, (token_in, token_out), amount_in)?;
+			Ok(amount_out)
+		}
+
+		/// Tokens `swap_tokens_for_exact_tokens` takes for `amount_out` of `token_out`.
+		pub fn quote_exact_out(
+			token_in: TokenIdOf<T>,
+			token_out: TokenIdOf<T>,
+			amount_out: BalanceOf<T>,
+		) -> Result<BalanceOf<T>, DispatchError> {
+			Self::check_if_valid_tokens(token_in, token_out)?;
+			let (_, pool) = Self::find_pool(token_in, token_out)?;
+			let (amount_in, _) = Self::price_swap_exact_out(&pool, (token_in, token_out), amount_out)?;
+			Ok(amount_in)
+		}
+
+		/// Tokens `deposit_liquidity` takes of at most `amount_a` and `amount_b`, in that order, and
+		/// the lp tokens it mints for them.
+		pub fn quote_add_liquidity(
+			token_a: TokenIdOf<T>,
+			token_b: TokenIdOf<T>,
+			amount_a: BalanceOf<T>,
+			amount_b: BalanceOf<T>,
+		) -> Result<(BalanceOf<T>, BalanceOf<T>, BalanceOf<T>), DispatchError> {
+			Self::check_if_valid_tokens(token_a, token_b)?;
+			let (_, pool) = Self::find_pool(token_a, token_b)?;
+			let deposit = Self::create_deposit(token_a, token_b, amount_a, amount_b, Zero::zero());
+			let quantities = (deposit.quantity_token_a, deposit.quantity_token_b);
+
+			let ((quantity_token_a, quantity_token_b), lp_reward) = if pool.lp_supply.is_zero() {
+				// The first deposit locks the minimum liquidity
+				let lp_minted = DexPricer::new_pool_function(quantities.0, quantities.1).map_err(Error::<T>::from)?;
+				let lp_reward = lp_minted.saturating_sub(T::MinimumLiquidity::get());
+				(quantities, lp_reward)
+			} else {
+				// Only the ratio of the pool is taken, after the protocol fee is minted
+				let quantities = DexPricer::optimal_deposit(quantities, (pool.reserve_a, pool.reserve_b))
+					.map_err(Error::<T>::from)?;
+				let lp_supply = pool.lp_supply
+					.checked_add(&Self::pending_protocol_fee(&pool)?)
+					.ok_or(Error::<T>::Overflow)?;
+				let lp_reward = DexPricer::existing_pool_function(quantities, (pool.reserve_a, pool.reserve_b), lp_supply)
+					.map_err(Error::<T>::from)?;
+				(quantities, lp_reward)
+			};
+			ensure!(!lp_reward.is_zero(), Error::<T>::InsufficientLiquidityMinted);
+
+			if token_a == pool.token_a {
+				Ok((quantity_token_a, quantity_token_b, lp_reward))
+			} else {
+				Ok((quantity_token_b, quantity_token_a, lp_reward))
+			}
+		}
+
+		/// Tokens `withdraw_liquidity` gives of `token_a` and `token_b`, in that order, for burning
+		/// `lp_amount`.
+		pub fn quote_remove_liquidity(
+			token_a: TokenIdOf<T>,
+			token_b: TokenIdOf<T>,
+			lp_amount: BalanceOf<T>,
+		) -> Result<(BalanceOf<T>, BalanceOf<T>), DispatchError> {
+			Self::check_if_withdrawable_tokens(token_a, token_b)?;
+			ensure!(!lp_amount.is_zero(), Error::<T>::NoTokens);
+			let (_, pool) = Self::find_pool(token_a, token_b)?;
+
+			// The protocol fee is minted before the withdrawal, the minimum liquidity always stays
+			let lp_minted = pool.lp_supply
+				.checked_add(&Self::pending_protocol_fee(&pool)?)
+				.ok_or(Error::<T>::Overflow)?;
+			let lp_remaining = lp_minted.checked_sub(&lp_amount).ok_or(Error::<T>::LiquidityBelowMinimum)?;
+			ensure!(lp_remaining >= T::MinimumLiquidity::get(), Error::<T>::LiquidityBelowMinimum);
+
+			let reward_a = DexPricer::liquidity_reward(lp_amount, lp_minted, pool.reserve_a).map_err(Error::<T>::from)?;
+			let reward_b = DexPricer::liquidity_reward(lp_amount, lp_minted, pool.reserve_b).map_err(Error::<T>::from)?;
+			if token_a == pool.token_a {
+				Ok((reward_a, reward_b))
+			} else {
+				Ok((reward_b, reward_a))
+			}
+		}
+
+		/// Account holding the reserves of the pool of `token_a` and `token_b`.
+		pub fn pool_account(token_a: TokenIdOf<T>, token_b: TokenIdOf<T>) -> Result<T::AccountId, DispatchError> {
+			let (_, pool) = Self::find_pool(token_a, token_b)?;
+			Ok(pool.account)
+		}
+
+		/// Lp token of the pool of `token_a` and `token_b`.
+		pub fn lp_token(token_a: TokenIdOf<T>, token_b: TokenIdOf<T>) -> Result<TokenIdOf<T>, DispatchError> {
+			let (_, pool) = Self::find_pool(token_a, token_b)?;
+			Ok(pool.lp_token)
+		}
+	}
+
+	/// Sorted token pair ID, k and lp supply (with the pending protocol fee) of every pool at some
+	/// point, to check the next state of the pools against.
+	#[cfg(any(feature = "try-runtime", test))]
+	pub type PoolSnapshot<T> = Vec<([u8; 16], U256, BalanceOf<T>)>;
+
+	/// Where `on_finalize` keeps the snapshot of the pools at the end of the last block.
+	#[cfg(any(feature = "try-runtime", test))]
+	const POOL_SNAPSHOT_KEY: &[u8] = b":dex:pool_snapshot";
+
+	// INVARIANTS
+	#[cfg(any(feature = "try-runtime", test))]
+	impl<T: Config> Pallet<T>
+		where TokenIdOf<T>: Ord + PartialOrd + AtLeast32Bit + Copy {
+		fn k(pool: &PoolInfoOf<T>) -> U256 {
+			U256::from(pool.reserve_a.saturated_into::<u128>()) * U256::from(pool.reserve_b.saturated_into::<u128>())
+		}
+
+		fn lp_shares(pool: &PoolInfoOf<T>) -> Result<BalanceOf<T>, &'static str> {
+			// The protocol fee is owed already, minting it doesn't change the value of a share
+			let protocol_lp = Self::pending_protocol_fee(pool).map_err(|_| "protocol fee can't be priced")?;
+			pool.lp_supply.checked_add(&protocol_lp).ok_or("lp supply overflows")
+		}
+
+		/// The state of every pool to compare the next `do_try_state` with.
+		pub fn pool_snapshot() -> Result<PoolSnapshot<T>, &'static str> {
+			Pools::<T>::iter()
+				.map(|(tokenpair_id, pool)| Ok((tokenpair_id, Self::k(&pool), Self::lp_shares(&pool)?)))
+				.collect()
+		}
+
+		/// Checks the invariants of every pool:
+		/// - both tokens are listed in `AllowedAssets`, which `remove_asset` keeps so for assets
+		///   that pools still use,
+		/// - the lp token exists and its total issuance is the tracked lp supply,
+		/// - the pool's account holds at least its reserves,
+		/// - k per lp token squared didn't decrease since `previous`, which holds for swaps,
+		///   deposits and withdrawals alike.
+		pub fn do_try_state(previous: &PoolSnapshot<T>) -> Result<(), &'static str> {
+			for (tokenpair_id, pool) in Pools::<T>::iter() {
+				ensure!(
+					AllowedAssets::<T>::contains_key(pool.token_a) && AllowedAssets::<T>::contains_key(pool.token_b),
+					"pool uses an asset that isn't listed"
+				);
+
+				// Lp tokens are created with a minimum balance, missing assets have none
+				ensure!(!T::Tokens::minimum_balance(pool.lp_token).is_zero(), "lp token doesn't exist");
+				ensure!(T::Tokens::total_issuance(pool.lp_token) == pool.lp_supply, "lp supply drifted from the lp token");
+
+				ensure!(
+					pool.reserve_a <= T::Tokens::balance(pool.token_a, &pool.account)
+						&& pool.reserve_b <= T::Tokens::balance(pool.token_b, &pool.account),
+					"reserves exceed the pool's balances"
+				);
+
+				// k_now * lp_before^2 >= k_before * lp_now^2, every lp token is worth at least as much
+				if let Some((_, k, lp_shares)) = previous.iter().find(|(id, _, _)| *id == tokenpair_id) {
+					let lp_before = U256::from((*lp_shares).saturated_into::<u128>());
+					let lp_now = U256::from(Self::lp_shares(&pool)?.saturated_into::<u128>());
+					ensure!(
+						Self::k(&pool).full_mul(lp_before * lp_before) >= k.full_mul(lp_now * lp_now),
+						"k per lp token decreased"
+					);
+				}
+			}
+			Ok(())
+		}
 	}
 }
\ No newline at end of file
@@ -0,0 +1,190 @@
+//! Storage migrations of the DEX pallet.
+//!
+//! Every change to the storage layout bumps `STORAGE_VERSION` and adds a module here with the
+//! migration from the previous version. The migrations check the on-chain storage version
+//! themselves, so `Migrations` can run all of them in order on every runtime upgrade.
+
+use crate::pallet::*;
+use codec::Decode;
+use frame_support::storage::{migration::{storage_key_iter, take_storage_value}, PrefixIterator};
+use frame_support::traits::{Get, GetStorageVersion, OnRuntimeUpgrade, PalletInfoAccess, StorageVersion};
+use frame_support::weights::Weight;
+use frame_support::{Blake2_128Concat, Hashable};
+use frame_support::traits::tokens::fungibles::{Inspect, Mutate};
+use sp_runtime::traits::{AtLeast32Bit, Zero};
+use sp_std::{collections::btree_map::BTreeMap, marker::PhantomData, vec::Vec};
+
+#[cfg(feature = "try-runtime")]
+use frame_support::traits::OnRuntimeUpgradeHelpersExt;
+
+/// All migrations of the pallet, oldest first.
+pub type Migrations<T> = (v1::MigrateToV1<T>,);
+
+pub mod v1 {
+	use super::*;
+
+	/// Moves the pools of v0 into `Pools`. v0 only kept the account of every pool in `AllPools`,
+	/// by token pair ID, and the accounts that deposited into it in `LiquidityProviders`. The
+	/// rest of the pool record is recovered from the chain:
+	/// - the token pair is the pair of allowed assets whose token pair ID matches,
+	/// - the lp token ID was decoded from the first bytes of the token pair ID,
+	/// - the reserves are the balances of the pool's account,
+	/// - the lp supply is the copy of every lp token v0 minted into the pool's account, which is
+	///   burned so only the lp tokens of the providers remain.
+	///
+	/// Migrated pools don't lock `MinimumLiquidity` and were created at the upgrade's block.
+	///
+	/// Pools whose token pair isn't listed, or whose lp tokens can't be burned, are left in
+	/// `AllPools` with an error in the log, and the storage version stays at 0. List their assets
+	/// and upgrade again to migrate them.
+	pub struct MigrateToV1<T>(PhantomData<T>);
+
+	/// Upper bound of the execution time of hashing a token pair ID or looking one up while
+	/// migrating a pool, 1 µs.
+	const STEP_WEIGHT: Weight = 1_000_000;
+
+	impl<T: Config> MigrateToV1<T> {
+		fn pallet_prefix() -> &'static [u8] {
+			<Pallet<T> as PalletInfoAccess>::name().as_bytes()
+		}
+
+		fn v0_pools() -> PrefixIterator<([u8; 16], T::AccountId)> {
+			storage_key_iter::<[u8; 16], T::AccountId, Blake2_128Concat>(Self::pallet_prefix(), b"AllPools")
+		}
+	}
+
+	impl<T: Config> OnRuntimeUpgrade for MigrateToV1<T>
+		where TokenIdOf<T>: AtLeast32Bit {
+		fn on_runtime_upgrade() -> Weight {
+			if Pallet::<T>::on_chain_storage_version() >= 1 {
+				return T::DbWeight::get().reads(1);
+			}
+			let mut reads: Weight = 2;
+			let mut writes: Weight = 1;
+
+			// v0 only allowed DOT, ETH, ADA and BTC, which aren't listed yet on chains from before the
+			// asset registry
+			if AllowedAssets::<T>::iter_keys().next().is_none() {
+				for asset in 1u32..=4 {
+					AllowedAssets::<T>::insert(TokenIdOf::<T>::from(asset), AssetStatus::Active);
+					writes += 1;
+				}
+			}
+			let assets: Vec<TokenIdOf<T>> = AllowedAssets::<T>::iter_keys().collect();
+			reads += assets.len() as Weight;
+
+			// The token pair ID is a hash of the sorted token pair, hash every listed pair once
+			let mut pairs = BTreeMap::new();
+			for a in assets.iter() {
+				for b in assets.iter().filter(|b| a < *b) {
+					pairs.insert(Pallet::<T>::create_token_pair_id(*a, *b), (*a, *b));
+				}
+			}
+			let mut steps = pairs.len() as Weight;
+
+			let block_number = frame_system::Pallet::<T>::block_number();
+			let v0_pools: Vec<([u8; 16], T::AccountId)> = Self::v0_pools().collect();
+			let mut left = 0u32;
+			for (tokenpair_id, account) in v0_pools {
+				reads += 5;
+				steps += 1;
+
+				let (token_a, token_b) = match pairs.get(&tokenpair_id) {
+					Some(pair) => *pair,
+					None => {
+						log::error!(
+							target: "runtime::dex",
+							"v0 pool {:?} has no listed token pair, it isn't migrated",
+							account,
+						);
+						left += 1;
+						continue
+					},
+				};
+				let lp_token: TokenIdOf<T> = match u32::decode(&mut &tokenpair_id[..]) {
+					Ok(lp_token) => lp_token.into(),
+					Err(_) => {
+						log::error!(target: "runtime::dex", "v0 pool {:?} has no lp token, it isn't migrated", account);
+						left += 1;
+						continue
+					},
+				};
+
+				// Burn the pool's copy of the lp tokens, its amount is the lp supply
+				let lp_supply = T::Tokens::balance(lp_token, &account);
+				if !lp_supply.is_zero() {
+					if let Err(error) = T::Tokens::burn_from(lp_token, &account, lp_supply) {
+						log::error!(
+							target: "runtime::dex",
+							"burning the lp tokens of v0 pool {:?} failed: {:?}, it isn't migrated",
+							account,
+							error,
+						);
+						left += 1;
+						continue
+					}
+					writes += 2;
+				}
+
+				// Drop the v0 records, any holder of the lp tokens can withdraw now
+				let prefix = Self::pallet_prefix();
+				take_storage_value::<T::AccountId>(prefix, b"AllPools", &tokenpair_id.blake2_128_concat());
+				take_storage_value::<Vec<T::AccountId>>(prefix, b"LiquidityProviders", &account.blake2_128_concat());
+				writes += 3;
+
+				Pools::<T>::insert(&tokenpair_id, PoolInfo {
+					token_a,
+					token_b,
+					account: account.clone(),
+					reserve_a: T::Tokens::balance(token_a, &account),
+					reserve_b: T::Tokens::balance(token_b, &account),
+					lp_token,
+					lp_supply,
+					fee: T::SwapFee::get(),
+					created_at: block_number,
+				});
+				AssetPools::<T>::mutate(token_a, |pools| *pools = pools.saturating_add(1));
+				AssetPools::<T>::mutate(token_b, |pools| *pools = pools.saturating_add(1));
+				writes += 2;
+			}
+
+			// Pools left behind are migrated by the next upgrade
+			if left == 0 {
+				StorageVersion::new(1).put::<Pallet<T>>();
+			} else {
+				log::error!(target: "runtime::dex", "{} v0 pools left, storage version stays at 0", left);
+			}
+			T::DbWeight::get().reads_writes(reads, writes).saturating_add(steps.saturating_mul(STEP_WEIGHT))
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn pre_upgrade() -> Result<(), &'static str> {
+			if Pallet::<T>::on_chain_storage_version() < 1 {
+				let pools = Self::v0_pools().count() as u32;
+				Self::set_temp_storage(pools, "v0_pools");
+			}
+			Ok(())
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade() -> Result<(), &'static str> {
+			frame_support::ensure!(Pallet::<T>::on_chain_storage_version() >= 1, "storage version not bumped");
+			if let Some(pools) = Self::get_temp_storage::<u32>("v0_pools") {
+				frame_support::ensure!(Self::v0_pools().next().is_none(), "v0 pools left behind");
+				frame_support::ensure!(Pools::<T>::iter().count() as u32 == pools, "not every v0 pool migrated");
+				for pool in Pools::<T>::iter_values() {
+					frame_support::ensure!(
+						pool.reserve_a <= T::Tokens::balance(pool.token_a, &pool.account)
+							&& pool.reserve_b <= T::Tokens::balance(pool.token_b, &pool.account),
+						"pool reserves exceed its balances"
+					);
+					frame_support::ensure!(
+						T::Tokens::balance(pool.lp_token, &pool.account).is_zero(),
+						"pool still holds its copy of the lp tokens"
+					);
+				}
+			}
+			Ok(())
+		}
+	}
+}
@@ -1,10 +1,11 @@
 use crate as pallet_template;
-use frame_support::traits::{ConstU16, ConstU64, ConstU128, ConstU32};
+use frame_support::traits::{ConstU16, ConstU64, ConstU128, ConstU32, GenesisBuild, OnFinalize};
 use frame_system as system;
 use sp_core::H256;
 use sp_runtime::{
 	testing::Header,
 	traits::{BlakeTwo256, IdentityLookup},
+	Permill,
 };
 use pallet_assets;
 use pallet_balances;
@@ -24,7 +25,7 @@ frame_support::construct_runtime!(
 		UncheckedExtrinsic = UncheckedExtrinsic,
 	{
 		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
-		TemplateModule: pallet_template::{Pallet, Call, Storage, Event<T>},
+		TemplateModule: pallet_template::{Pallet, Call, Storage, Config<T>, Event<T>},
         Assets: pallet_assets,
         Balances: pallet_balances,
 	}
@@ -103,18 +104,45 @@ parameter_types! {
 
 parameter_types! {
 	pub DEX_pallet: PalletId = PalletId(*b"DEX_POOL");
+	pub const SwapFee: Permill = Permill::from_perthousand(3);
+	pub const TreasuryAccount: AccountId = 1_000;
+	pub const PoolCreationDeposit: Balance = 10_000;
+	pub const LpTokenIdStart: u32 = 1_000;
+	pub const MinimumLiquidity: Balance = 1_000;
 }
 
 impl pallet_template::Config for Test {
 	type Event = Event;
     type Tokens = Assets;
+	type AssetId = u32;
+	type AssetBalance = u128;
 	type Balances = Balances;
 	type PalletId = DEX_pallet;
-	type MaxLiqProviders = frame_support::pallet_prelude::ConstU32<4>;
+	type SwapFee = SwapFee;
+	type PoolCreationDeposit = PoolCreationDeposit;
+	type MinimumLiquidity = MinimumLiquidity;
+	type LpTokenIdStart = LpTokenIdStart;
+	type MaxLpTokenIdSkips = ConstU32<2>;
+	type MaxPathLength = ConstU32<4>;
+	type ProtocolFeeReceiver = TreasuryAccount;
+	type AdminOrigin = EnsureRoot<AccountId>;
 }
 
 
 // Build genesis storage according to the mock runtime.
 pub fn new_test_ext() -> sp_io::TestExternalities {
-	system::GenesisConfig::default().build_storage::<Test>().unwrap().into()
-}
\ No newline at end of file
+	let mut t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	// DOT, ETH, ADA, BTC
+	pallet_template::GenesisConfig::<Test> { allowed_assets: vec![1, 2, 3, 4], pools: vec![] }
+		.assimilate_storage(&mut t)
+		.unwrap();
+	t.into()
+}
+
+/// Finalize the blocks up to `n`, which checks the pools at the end of each of them.
+pub fn run_to_block(n: u64) {
+	while System::block_number() < n {
+		TemplateModule::on_finalize(System::block_number());
+		System::set_block_number(System::block_number() + 1);
+	}
+}
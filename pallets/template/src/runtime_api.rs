@@ -0,0 +1,41 @@
+//! Runtime API of the DEX pallet, to price trades and find pools without submitting extrinsics.
+
+use codec::Codec;
+
+sp_api::decl_runtime_apis! {
+	/// Reserves, quotes and pools of the DEX. The quotes are priced by the same code as the
+	/// extrinsics, at the state of the block they are called at. `None` if there is no pool of
+	/// the tokens, the extrinsic doesn't take the tokens or would fail to price the trade.
+	pub trait DexApi<AccountId, AssetId, Balance>
+	where
+		AccountId: Codec,
+		AssetId: Codec,
+		Balance: Codec,
+	{
+		/// Reserves of the pool of `token_a` and `token_b`, in that order.
+		fn get_reserves(token_a: AssetId, token_b: AssetId) -> Option<(Balance, Balance)>;
+
+		/// Tokens out of swapping `amount_in` of `token_in` for `token_out`.
+		fn quote_exact_in(token_in: AssetId, token_out: AssetId, amount_in: Balance) -> Option<Balance>;
+
+		/// Tokens in to swap `token_in` for exactly `amount_out` of `token_out`.
+		fn quote_exact_out(token_in: AssetId, token_out: AssetId, amount_out: Balance) -> Option<Balance>;
+
+		/// Tokens taken of at most `amount_a` and `amount_b` and the lp tokens minted for them.
+		fn quote_add_liquidity(
+			token_a: AssetId,
+			token_b: AssetId,
+			amount_a: Balance,
+			amount_b: Balance,
+		) -> Option<(Balance, Balance, Balance)>;
+
+		/// Tokens of `token_a` and `token_b` given for burning `lp_amount`.
+		fn quote_remove_liquidity(token_a: AssetId, token_b: AssetId, lp_amount: Balance) -> Option<(Balance, Balance)>;
+
+		/// Account holding the reserves of the pool of `token_a` and `token_b`.
+		fn pool_account(token_a: AssetId, token_b: AssetId) -> Option<AccountId>;
+
+		/// Lp token of the pool of `token_a` and `token_b`.
+		fn lp_token(token_a: AssetId, token_b: AssetId) -> Option<AssetId>;
+	}
+}
@@ -1,9 +1,12 @@
-use crate::{mock::*, Error};
+use crate::{dex_pricer::{DexPricer, PricerError}, mock::*, Error};
 use frame_support::traits::Currency;
 use frame_support::traits::fungibles::Mutate;
 use frame_support::pallet_prelude::*;
 use frame_support::{assert_noop, assert_ok};
 use frame_support::Hashable;
+use frame_support::storage::migration::{get_storage_value, put_storage_value};
+use frame_support::traits::{GenesisBuild, OnRuntimeUpgrade, ReservableCurrency};
+use sp_runtime::{traits::AccountIdConversion, PerThing, Permill};
 
 const USER: AccountId = 1;
 const USER2: AccountId = 2;
@@ -18,6 +21,7 @@ const A_LOT: u128 = 1_000_000_000_000;
 const TOO_MUCH: u128 = 1_000_000_000_000_000_000_000_000_000_000u128;
 const PLEDGE: u128 = 50_000_000;
 const NOT_ENOUGH: u128 = 49_000_000;
+const DEADLINE: u64 = 100;
 
 #[derive(Debug, PartialEq)]
 pub struct Withdrawal {
@@ -55,18 +59,24 @@ fn create_token_pair_id(token_a: u32, token_b: u32) -> [u8; 16] {
 	pool_id
 }
 
+fn pool_account(token_a: u32, token_b: u32) -> AccountId {
+	let mut tokenpair = vec![token_a, token_b];
+	tokenpair.sort();
+	DEX_pallet::get().into_sub_account_truncating(&create_token_pair_id(tokenpair[0], tokenpair[1]))
+}
+
 #[test]
 fn test_identicaltokens_error() {
     new_test_ext().execute_with(|| {
 		let origin = Origin::signed(USER);
 
-		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(USER), DOT, DOT, PLEDGE, PLEDGE), Error::<Test>::IdenticalTokens);
-		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(USER), BTC, BTC, PLEDGE, PLEDGE), Error::<Test>::IdenticalTokens);
-		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(USER), NOASSET1, NOASSET1, PLEDGE, PLEDGE), Error::<Test>::IdenticalTokens);
+		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(USER), DOT, DOT, PLEDGE, PLEDGE, 0, DEADLINE), Error::<Test>::IdenticalTokens);
+		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(USER), BTC, BTC, PLEDGE, PLEDGE, 0, DEADLINE), Error::<Test>::IdenticalTokens);
+		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(USER), NOASSET1, NOASSET1, PLEDGE, PLEDGE, 0, DEADLINE), Error::<Test>::IdenticalTokens);
 		
-		assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(USER), ETH, ETH, LP), Error::<Test>::IdenticalTokens);
-		assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(USER), ADA, ADA, LP), Error::<Test>::IdenticalTokens);
-		assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(USER), NOASSET2, NOASSET2, LP), Error::<Test>::IdenticalTokens);
+		assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(USER), ETH, ETH, LP, PLEDGE, 0, 0, DEADLINE), Error::<Test>::IdenticalTokens);
+		assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(USER), ADA, ADA, LP, PLEDGE, 0, 0, DEADLINE), Error::<Test>::IdenticalTokens);
+		assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(USER), NOASSET2, NOASSET2, LP, PLEDGE, 0, 0, DEADLINE), Error::<Test>::IdenticalTokens);
 	});
 }
 
@@ -74,17 +84,17 @@ fn test_identicaltokens_error() {
 fn test_invalidtoken_error() {
     new_test_ext().execute_with(|| {
 
-		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(USER), NOASSET1, ETH, PLEDGE, PLEDGE), Error::<Test>::InvalidToken);
-		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(USER), DOT, NOASSET2, PLEDGE, PLEDGE), Error::<Test>::InvalidToken);
-		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(USER), NOASSET1, NOASSET2, PLEDGE, PLEDGE), Error::<Test>::InvalidToken);
+		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(USER), NOASSET1, ETH, PLEDGE, PLEDGE, 0, DEADLINE), Error::<Test>::InvalidToken);
+		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(USER), DOT, NOASSET2, PLEDGE, PLEDGE, 0, DEADLINE), Error::<Test>::InvalidToken);
+		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(USER), NOASSET1, NOASSET2, PLEDGE, PLEDGE, 0, DEADLINE), Error::<Test>::InvalidToken);
 
-		assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(USER), NOASSET1, ETH, LP), Error::<Test>::InvalidToken);
-		assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(USER), DOT, NOASSET2, LP), Error::<Test>::InvalidToken);
-		assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(USER), NOASSET1, NOASSET2, LP), Error::<Test>::InvalidToken);
+		assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(USER), NOASSET1, ETH, LP, PLEDGE, 0, 0, DEADLINE), Error::<Test>::InvalidToken);
+		assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(USER), DOT, NOASSET2, LP, PLEDGE, 0, 0, DEADLINE), Error::<Test>::InvalidToken);
+		assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(USER), NOASSET1, NOASSET2, LP, PLEDGE, 0, 0, DEADLINE), Error::<Test>::InvalidToken);
 
-		assert_noop!(TemplateModule::swap(Origin::signed(USER), NOASSET1, ETH, PLEDGE), Error::<Test>::InvalidToken);
-		assert_noop!(TemplateModule::swap(Origin::signed(USER), DOT, NOASSET2, PLEDGE), Error::<Test>::InvalidToken);
-		assert_noop!(TemplateModule::swap(Origin::signed(USER), NOASSET1, NOASSET2, PLEDGE), Error::<Test>::InvalidToken);
+		assert_noop!(TemplateModule::swap(Origin::signed(USER), NOASSET1, ETH, PLEDGE, 0, DEADLINE), Error::<Test>::InvalidToken);
+		assert_noop!(TemplateModule::swap(Origin::signed(USER), DOT, NOASSET2, PLEDGE, 0, DEADLINE), Error::<Test>::InvalidToken);
+		assert_noop!(TemplateModule::swap(Origin::signed(USER), NOASSET1, NOASSET2, PLEDGE, 0, DEADLINE), Error::<Test>::InvalidToken);
 	});
 }
 
@@ -92,8 +102,8 @@ fn test_invalidtoken_error() {
 fn test_notenoughfunds_error() {
     new_test_ext().execute_with(|| {
 		let user = create_user_with_two_assets(USER, DOT, ETH, NOT_ENOUGH);
-        assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE), Error::<Test>::NotEnoughFunds);
-        assert_noop!(TemplateModule::swap(Origin::signed(user), DOT, ETH, PLEDGE), Error::<Test>::NotEnoughFunds);
+        assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE), Error::<Test>::NotEnoughFunds);
+        assert_noop!(TemplateModule::swap(Origin::signed(user), DOT, ETH, PLEDGE, 0, DEADLINE), Error::<Test>::NotEnoughFunds);
     });
 }
 
@@ -101,7 +111,7 @@ fn test_notenoughfunds_error() {
 fn test_notenoughfundstokena_error() {
     new_test_ext().execute_with(|| {
 		let user = create_user_with_two_assets(USER, DOT, ETH, PLEDGE);
-        assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, A_LOT, PLEDGE), Error::<Test>::NotEnoughFundsTokenA);
+        assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, A_LOT, PLEDGE, 0, DEADLINE), Error::<Test>::NotEnoughFundsTokenA);
 	});
 }
 
@@ -109,7 +119,7 @@ fn test_notenoughfundstokena_error() {
 fn test_notenoughfundstokenb_error() {
     new_test_ext().execute_with(|| {
 		let user = create_user_with_two_assets(USER, DOT, ETH, PLEDGE);
-        assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, A_LOT), Error::<Test>::NotEnoughFundsTokenB);
+        assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, A_LOT, 0, DEADLINE), Error::<Test>::NotEnoughFundsTokenB);
 	});
 }
 
@@ -117,45 +127,230 @@ fn test_notenoughfundstokenb_error() {
 fn test_deposit_ok() {
     new_test_ext().execute_with(|| {
 		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
-        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE));
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+    });
+}
+
+#[test]
+fn test_create_pool() {
+    new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let lp_token_id = LpTokenIdStart::get();
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+        assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE), Error::<Test>::PoolNotFound);
+        assert_noop!(TemplateModule::create_pool(Origin::signed(user), DOT, DOT), Error::<Test>::IdenticalTokens);
+        assert_noop!(TemplateModule::create_pool(Origin::signed(user), DOT, NOASSET2), Error::<Test>::InvalidToken);
+
+		// The creator pays the deposit and the existential deposit of the pool
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), ETH, DOT));
+		let pool = pool_account(DOT, ETH);
+		assert_eq!(Balances::reserved_balance(&user), PoolCreationDeposit::get());
+		assert_eq!(Balances::free_balance(&user), A_LOT - PoolCreationDeposit::get() - ExistentialDeposit::get());
+		assert_eq!(Balances::free_balance(&pool), ExistentialDeposit::get());
+		System::assert_last_event(Event::TemplateModule(crate::Event::<Test>::PoolCreated {
+			creator: user,
+			pool,
+			token_a: DOT,
+			token_b: ETH,
+			lp_token: lp_token_id,
+		}));
+        assert_noop!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH), Error::<Test>::PoolAlreadyExists);
+
+		// Nothing is minted until the first deposit
+		let info = crate::Pools::<Test>::get(create_token_pair_id(DOT, ETH)).unwrap();
+		assert_eq!((info.token_a, info.token_b, info.lp_token), (DOT, ETH, lp_token_id));
+		assert_eq!((info.reserve_a, info.reserve_b, info.lp_supply), (0, 0, 0));
+		assert_eq!((info.fee, info.created_at), (SwapFee::get(), 1));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		assert_eq!(Assets::balance(lp_token_id, &user), PLEDGE - MinimumLiquidity::get());
+    });
+}
+
+#[test]
+fn test_lp_token_ids() {
+    new_test_ext().execute_with(|| {
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+		let user = create_user_with_one_asset(user, BTC, A_LOT);
+		assert_noop!(TemplateModule::add_asset(Origin::root(), LpTokenIdStart::get()), Error::<Test>::AssetIdReserved);
+
+		// Every pool gets the next lp token ID of the reserved range
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), BTC, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), ETH, BTC, PLEDGE, 4 * PLEDGE, 0, DEADLINE));
+		assert_eq!(Assets::balance(LpTokenIdStart::get(), &user), PLEDGE - MinimumLiquidity::get());
+		assert_eq!(Assets::balance(LpTokenIdStart::get() + 1, &user), 2 * PLEDGE - MinimumLiquidity::get());
+    });
+}
+
+#[test]
+fn test_lp_token_id_taken() {
+    new_test_ext().execute_with(|| {
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+
+		// Someone else created the asset of the next lp token ID
+		assert_ok!(Assets::force_create(Origin::root(), LpTokenIdStart::get(), USER2, true, 1));
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+		let pool = crate::Pools::<Test>::get(create_token_pair_id(DOT, ETH)).unwrap();
+		assert_eq!(pool.lp_token, LpTokenIdStart::get() + 1);
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		assert_eq!(Assets::balance(LpTokenIdStart::get() + 1, &user), PLEDGE - MinimumLiquidity::get());
+    });
+}
+
+#[test]
+fn test_lp_token_ids_squatted() {
+    new_test_ext().execute_with(|| {
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+		let skips = <Test as crate::Config>::MaxLpTokenIdSkips::get();
+		let lp_token_id = LpTokenIdStart::get();
+
+		// Skipping as many IDs taken by someone else as the weight pays for is fine
+		for id in lp_token_id..lp_token_id + skips {
+			assert_ok!(Assets::force_create(Origin::root(), id, USER2, true, 1));
+		}
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+		let pool = crate::Pools::<Test>::get(create_token_pair_id(DOT, ETH)).unwrap();
+		assert_eq!(pool.lp_token, lp_token_id + skips);
+
+		// Any more can't be skipped
+		for id in lp_token_id + skips + 1..=lp_token_id + 2 * skips + 1 {
+			assert_ok!(Assets::force_create(Origin::root(), id, USER2, true, 1));
+		}
+        assert_noop!(TemplateModule::create_pool(Origin::signed(user), DOT, ADA), Error::<Test>::LpTokenIdsExhausted);
+    });
+}
+
+#[test]
+fn test_minimum_liquidity_locked() {
+    new_test_ext().execute_with(|| {
+		let lp_token_id = LpTokenIdStart::get();
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+
+		// sqrt(1_000 * 1_000) lp tokens would all be locked
+        assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, 1_000, 1_000, 0, DEADLINE), Error::<Test>::InsufficientLiquidityMinted);
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, 1_001, 1_001, 0, DEADLINE));
+		assert_eq!(Assets::balance(lp_token_id, &user), 1);
+		assert_eq!(Assets::balance(lp_token_id, &pool_account(DOT, ETH)), MinimumLiquidity::get());
+		let info = crate::Pools::<Test>::get(create_token_pair_id(DOT, ETH)).unwrap();
+		assert_eq!(info.lp_supply, 1_001);
     });
 }
 
 #[test]
-fn test_maxliqproviders_error() {
+fn test_many_liquidity_providers() {
     new_test_ext().execute_with(|| {
 		let user1 = create_user_with_two_assets(1, DOT, ETH, A_LOT);
-        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user1), DOT, ETH, PLEDGE, PLEDGE));
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user1), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user1), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
 
 		let user2 = create_user_with_two_assets(2, DOT, ETH, A_LOT);
-        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user2), DOT, ETH, PLEDGE, PLEDGE));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user2), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
 
 		let user3 = create_user_with_two_assets(3, DOT, ETH, A_LOT);
-        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user3), DOT, ETH, PLEDGE, PLEDGE));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user3), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
 
 		let user4 = create_user_with_two_assets(4, DOT, ETH, A_LOT);
-        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user4), DOT, ETH, PLEDGE, PLEDGE));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user4), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
 		
 		let user5 = create_user_with_two_assets(5, DOT, ETH, A_LOT);
-        assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(user5), DOT, ETH, PLEDGE, PLEDGE), Error::<Test>::LiqProvidersOverflow);
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user5), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
     });
 }
 
 #[test]
-fn test_mathoverflow_error() {
+fn test_large_amounts() {
     new_test_ext().execute_with(|| {
-		// Some how this test does not pass, but it gives me the correct MathProblem error
-		let user = create_user_with_two_assets(USER, DOT, ETH, TOO_MUCH);
-		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, TOO_MUCH, TOO_MUCH), Error::<Test>::MathProblem);
+		// TOO_MUCH * TOO_MUCH doesn't fit in a u128, but the lp tokens do
+		let lp_token_id = LpTokenIdStart::get();
+		let user = create_user_with_two_assets(USER, DOT, ETH, 2 * TOO_MUCH);
+		assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+		assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, TOO_MUCH, TOO_MUCH, 0, DEADLINE));
+		assert_eq!(Assets::balance(lp_token_id, &user), TOO_MUCH - MinimumLiquidity::get());
+		assert_ok!(TemplateModule::swap(Origin::signed(user), DOT, ETH, TOO_MUCH, 0, DEADLINE));
+		assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, lp_token_id, TOO_MUCH / 2, 0, 0, DEADLINE));
+
+		// Results that don't fit are still rejected
+		assert_eq!(DexPricer::existing_pool_function((u128::MAX, u128::MAX), (1, 1), 2), Err(PricerError::Overflow));
     });
 }
 
+/// Tiny xorshift generator, so the pricer is checked over the whole u128 range without extra
+/// dependencies. Every value gets a random number of bits to cover all orders of magnitude.
+struct Samples(u128);
+
+impl Samples {
+	fn next(&mut self) -> u128 {
+		self.0 ^= self.0 << 35;
+		self.0 ^= self.0 >> 59;
+		self.0 ^= self.0 << 73;
+		self.0 >> (self.0 % 128)
+	}
+}
+
+fn pricer_samples() -> Vec<u128> {
+	let mut samples = vec![
+		1, 2, 3, 999, 1_000, PLEDGE, A_LOT, 1_000_000_000_000_000_000, u64::MAX as u128,
+		1 << 96, TOO_MUCH, u128::MAX / 3, u128::MAX - 1, u128::MAX,
+	];
+	let mut random = Samples(0x2545_f491_4f6c_dd1d_9e37_79b9_7f4a_7c15);
+	samples.extend((0..30).map(|_| random.next().max(1)));
+	samples
+}
+
+fn u512(value: u128) -> sp_core::U512 {
+	sp_core::U512::from(value)
+}
+
+#[test]
+fn test_pricer_full_range() {
+	let samples = pricer_samples();
+	let fees = [Permill::zero(), Permill::from_perthousand(3), Permill::from_percent(99)];
+	for &a in &samples {
+		for &b in &samples {
+			// sqrt(a * b), rounded down
+			let root = u512(DexPricer::new_pool_function(a, b).unwrap());
+			assert!(root * root <= u512(a) * u512(b));
+			assert!((root + 1) * (root + 1) > u512(a) * u512(b));
+
+			for &c in &samples {
+				// a * c / b, only an error if it doesn't fit
+				match DexPricer::liquidity_reward(a, b, c) {
+					Ok(x) => {
+						assert!(u512(x) * u512(b) <= u512(a) * u512(c));
+						assert!((u512(x) + 1) * u512(b) > u512(a) * u512(c));
+					},
+					Err(_) => assert!(u512(a) * u512(c) / u512(b) > u512(u128::MAX)),
+				}
+				if let Ok(x) = DexPricer::existing_pool_function((a, c), (b, b), c) {
+					assert!(u512(x) * u512(b) <= u512(a) * u512(c));
+				}
+
+				// Swaps never decrease k and never drain the pool
+				for fee in fees {
+					if let Ok((out, fee_amount)) = DexPricer::swap(a, (b, c), fee) {
+						assert_eq!(fee_amount, fee.mul_ceil(a));
+						assert!(out < c);
+						assert!((u512(b) + u512(a - fee_amount)) * u512(c - out) >= u512(b) * u512(c));
+					}
+					if let Ok((amount_in, fee_amount)) = DexPricer::swap_exact_out(a, (b, c), fee) {
+						assert!(a < c && fee_amount <= amount_in);
+						assert!((u512(b) + u512(amount_in - fee_amount)) * u512(c - a) >= u512(b) * u512(c));
+					}
+				}
+			}
+		}
+	}
+}
+
 #[test]
 fn test_nolptokens_error() {
     new_test_ext().execute_with(|| {
-        assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(USER), DOT, ETH, LP), Error::<Test>::NoTokens);
+        assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(USER), DOT, ETH, LP, PLEDGE, 0, 0, DEADLINE), Error::<Test>::NoTokens);
 		let user = create_user_with_one_asset(USER, LP, 0);
-        assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, LP), Error::<Test>::NoTokens);
+        assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, LP, PLEDGE, 0, 0, DEADLINE), Error::<Test>::NoTokens);
     });
 }
 
@@ -165,31 +360,109 @@ fn test_poolnotfound_error() {
 		// Hacky way of testing the check_if_valid_tokens function without depositing first and letting
 		// the lp token exist
 		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
-        assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, ETH), Error::<Test>::PoolNotFound);
-        assert_noop!(TemplateModule::swap(Origin::signed(user), DOT, ETH, PLEDGE), Error::<Test>::PoolNotFound);
+        assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, ETH, PLEDGE, 0, 0, DEADLINE), Error::<Test>::PoolNotFound);
+        assert_noop!(TemplateModule::swap(Origin::signed(user), DOT, ETH, PLEDGE, 0, DEADLINE), Error::<Test>::PoolNotFound);
     });
 }
 
 #[test]
-fn test_noliquidityprovided_error() {
+fn test_lp_tokens_fungible() {
     new_test_ext().execute_with(|| {
+		let lp_token_id = LpTokenIdStart::get();
 		let user1 = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
-        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user1), DOT, ETH, PLEDGE, PLEDGE));
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user1), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user1), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
 		let user2 = create_user_with_one_asset(USER2, ETH, A_LOT);
-        assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(user2), DOT, ETH, ETH), Error::<Test>::NoLiquidityProvided);
+        assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(user2), DOT, ETH, ETH, PLEDGE, 0, 0, DEADLINE), Error::<Test>::NoTokens);
+
+		// Lp tokens from someone else can be withdrawn as well
+		assert_ok!(Assets::transfer(Origin::signed(user1), lp_token_id, user2, PLEDGE / 2));
+        assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user2), DOT, ETH, lp_token_id, PLEDGE / 2, 0, 0, DEADLINE));
+		assert_eq!(Assets::balance(lp_token_id, &user2), 0);
+		assert_eq!(Assets::balance(DOT, &user2), PLEDGE / 2);
+		assert_eq!(Assets::balance(ETH, &user2), A_LOT + PLEDGE / 2);
     });
 }
 
 #[test]
 fn test_withdrawal_ok() {
     new_test_ext().execute_with(|| {
-		let tokenpair_id = create_token_pair_id(DOT, ETH);
-		let lp_token_id = u32::decode(&mut &*tokenpair_id.to_vec()).unwrap();
+		let lp_token_id = LpTokenIdStart::get();
 		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
-        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE));
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
 		let user2 = create_user_with_two_assets(USER2, DOT, ETH, A_LOT);
-        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user2), DOT, ETH, PLEDGE, PLEDGE));
-        assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, lp_token_id));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user2), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+        assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, lp_token_id, PLEDGE - MinimumLiquidity::get(), 0, 0, DEADLINE));
+    });
+}
+
+#[test]
+fn test_partial_withdrawal() {
+    new_test_ext().execute_with(|| {
+		let lp_token_id = LpTokenIdStart::get();
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		let lp_tokens = Assets::balance(lp_token_id, &user);
+        assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, lp_token_id, 0, 0, 0, DEADLINE), Error::<Test>::NoTokens);
+        assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, lp_token_id, lp_tokens + 1, 0, 0, DEADLINE), Error::<Test>::NotEnoughFunds);
+
+		// Half of the lp tokens minted is half of the pool
+        assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, lp_token_id, PLEDGE / 2, 0, 0, DEADLINE));
+		assert_eq!(Assets::balance(lp_token_id, &user), lp_tokens - PLEDGE / 2);
+		assert_eq!(Assets::balance(DOT, &user), A_LOT - PLEDGE / 2);
+		assert_eq!(Assets::balance(ETH, &user), A_LOT - PLEDGE / 2);
+
+		// Everything but the minimum liquidity can be withdrawn
+        assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, lp_token_id, lp_tokens - PLEDGE / 2, 0, 0, DEADLINE));
+		let info = crate::Pools::<Test>::get(create_token_pair_id(DOT, ETH)).unwrap();
+		assert_eq!((info.reserve_a, info.reserve_b, info.lp_supply), (MinimumLiquidity::get(), MinimumLiquidity::get(), MinimumLiquidity::get()));
+    });
+}
+
+#[test]
+fn test_liquidity_events() {
+    new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let lp_token_id = LpTokenIdStart::get();
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+		let pool = pool_account(DOT, ETH);
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+
+		// The tokens are given in any order, the events follow the pool's order
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), ETH, DOT, 2 * PLEDGE, PLEDGE, 0, DEADLINE));
+		System::assert_last_event(Event::TemplateModule(crate::Event::<Test>::LiquidityDeposited {
+			who: user,
+			pool,
+			token_a: DOT,
+			token_b: ETH,
+			amount_a: PLEDGE,
+			amount_b: 2 * PLEDGE,
+			lp_token: lp_token_id,
+			lp_minted: 70_710_678 - MinimumLiquidity::get(),
+			reserve_a: PLEDGE,
+			reserve_b: 2 * PLEDGE,
+		}));
+
+        assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, lp_token_id, 7_071_067, 0, 0, DEADLINE));
+		System::assert_last_event(Event::TemplateModule(crate::Event::<Test>::LiquidityWithdrawn {
+			who: user,
+			pool,
+			token_a: DOT,
+			token_b: ETH,
+			amount_a: 4_999_999,
+			amount_b: 9_999_998,
+			lp_token: lp_token_id,
+			lp_burned: 7_071_067,
+			reserve_a: PLEDGE - 4_999_999,
+			reserve_b: 2 * PLEDGE - 9_999_998,
+		}));
+
+		// Every event about the pool can be found by the pool's topic
+		let topic = TemplateModule::pool_topic(&pool);
+		let pool_events = System::events().into_iter().filter(|record| record.topics == vec![topic]).count();
+		assert_eq!(pool_events, 3);
     });
 }
 
@@ -197,7 +470,663 @@ fn test_withdrawal_ok() {
 fn test_swap_ok() {
     new_test_ext().execute_with(|| {
 		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
-        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE));
-		assert_ok!(TemplateModule::swap(Origin::signed(user), DOT, ETH, NOT_ENOUGH));
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		assert_ok!(TemplateModule::swap(Origin::signed(user), DOT, ETH, NOT_ENOUGH, 0, DEADLINE));
+    });
+}
+
+#[test]
+fn test_swap_amount_errors() {
+    new_test_ext().execute_with(|| {
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		assert_noop!(TemplateModule::swap(Origin::signed(user), DOT, ETH, 0, 0, DEADLINE), Error::<Test>::InsufficientInputAmount);
+		// The fee takes the single token, nothing is left to price
+		assert_noop!(TemplateModule::swap(Origin::signed(user), DOT, ETH, 1, 0, DEADLINE), Error::<Test>::InsufficientOutputAmount);
+		assert_noop!(TemplateModule::swap_tokens_for_exact_tokens(Origin::signed(user), DOT, ETH, 0, A_LOT, DEADLINE), Error::<Test>::InsufficientOutputAmount);
+    });
+}
+
+#[test]
+fn test_swap_uses_pool_reserves() {
+    new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		assert_ok!(TemplateModule::swap(Origin::signed(user), DOT, ETH, NOT_ENOUGH, 0, DEADLINE));
+
+		// 0.3% fee of 49_000_000 is 147_000
+		// 48_853_000 * 50_000_000 / (50_000_000 + 48_853_000)
+		assert_eq!(Assets::balance(DOT, &user), A_LOT - PLEDGE - NOT_ENOUGH);
+		assert_eq!(Assets::balance(ETH, &user), A_LOT - PLEDGE + 24_709_922);
+		System::assert_last_event(Event::TemplateModule(crate::Event::<Test>::SwapOccured {
+			who: user,
+			pool: pool_account(DOT, ETH),
+			token_in: DOT,
+			token_out: ETH,
+			amount_in: NOT_ENOUGH,
+			amount_out: 24_709_922,
+			fee: 147_000,
+			reserve_in: PLEDGE + NOT_ENOUGH,
+			reserve_out: PLEDGE - 24_709_922,
+		}));
+    });
+}
+
+#[test]
+fn test_pool_info_tracks_reserves() {
+    new_test_ext().execute_with(|| {
+		let lp_token_id = LpTokenIdStart::get();
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		let info = crate::Pools::<Test>::get(create_token_pair_id(DOT, ETH)).unwrap();
+		assert_eq!((info.reserve_a, info.reserve_b, info.lp_supply), (PLEDGE, PLEDGE, PLEDGE));
+		assert_eq!(Assets::balance(lp_token_id, &pool_account(DOT, ETH)), MinimumLiquidity::get());
+
+		// Tokens sent to the pool's account directly don't change the price
+		assert_ok!(Assets::transfer(Origin::signed(user), DOT, pool_account(DOT, ETH), PLEDGE));
+		assert_ok!(TemplateModule::swap(Origin::signed(user), DOT, ETH, NOT_ENOUGH, 0, DEADLINE));
+		assert_eq!(Assets::balance(ETH, &user), A_LOT - PLEDGE + 24_709_922);
+		let info = crate::Pools::<Test>::get(create_token_pair_id(DOT, ETH)).unwrap();
+		assert_eq!((info.reserve_a, info.reserve_b), (PLEDGE + NOT_ENOUGH, PLEDGE - 24_709_922));
+    });
+}
+
+#[test]
+fn test_skim_and_sync() {
+    new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+		let pool = pool_account(DOT, ETH);
+		assert_noop!(TemplateModule::sync(Origin::signed(user), DOT, ETH), Error::<Test>::PoolNotFound);
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+
+		// Nothing to skim
+		assert_ok!(TemplateModule::skim(Origin::signed(USER2), ETH, DOT, USER2));
+		System::assert_last_event(Event::TemplateModule(crate::Event::<Test>::Skimmed {
+			pool,
+			to: USER2,
+			amount_a: 0,
+			amount_b: 0,
+		}));
+
+		// Tokens sent by accident can be recovered by anyone
+		Balances::make_free_balance_be(&USER2, PLEDGE);
+		assert_ok!(Assets::transfer(Origin::signed(user), DOT, pool, NOT_ENOUGH));
+		assert_ok!(TemplateModule::skim(Origin::signed(USER2), DOT, ETH, USER2));
+		assert_eq!(Assets::balance(DOT, &USER2), NOT_ENOUGH);
+		assert_eq!(Assets::balance(DOT, &pool), PLEDGE);
+
+		// Or be added to the reserves
+		assert_ok!(Assets::transfer(Origin::signed(user), ETH, pool, NOT_ENOUGH));
+		assert_ok!(TemplateModule::sync(Origin::signed(USER2), DOT, ETH));
+		System::assert_last_event(Event::TemplateModule(crate::Event::<Test>::Synced {
+			pool,
+			reserve_a: PLEDGE,
+			reserve_b: PLEDGE + NOT_ENOUGH,
+		}));
+		let info = crate::Pools::<Test>::get(create_token_pair_id(DOT, ETH)).unwrap();
+		assert_eq!((info.reserve_a, info.reserve_b), (PLEDGE, PLEDGE + NOT_ENOUGH));
+    });
+}
+
+#[test]
+fn test_rounding_policy() {
+	let fee = Permill::from_perthousand(3);
+	// Fees round up: 0.3% of 7_777 is 23.331
+	assert_eq!(DexPricer::swap(7_777u128, (PLEDGE, PLEDGE), fee).unwrap().1, 24);
+	// Swap output rounds down: 997 * 3 / (3 + 997) is 2.991
+	assert_eq!(DexPricer::swap(1_000u128, (3, 3), fee), Ok((2, 3)));
+	// Swap input rounds up: 3 * 1 / (4 - 1) is 1 exactly, 5 * 1 / (4 - 1) is 1.67
+	assert_eq!(DexPricer::swap_exact_out(1u128, (3, 4), Permill::zero()), Ok((1, 0)));
+	assert_eq!(DexPricer::swap_exact_out(1u128, (5, 4), Permill::zero()), Ok((2, 0)));
+	// Lp tokens and withdrawals round down: 2 / 3 of a token or lp token is nothing
+	assert_eq!(DexPricer::existing_pool_function((1u128, 1), (3, 3), 2), Ok(0));
+	assert_eq!(DexPricer::liquidity_reward(2u128, 3, 1), Ok(0));
+	assert_eq!(DexPricer::new_pool_function(2u128, 4), Ok(2));
+}
+
+#[test]
+fn test_rounding_never_drains_pool() {
+    new_test_ext().execute_with(|| {
+		let lp_token_id = LpTokenIdStart::get();
+		let tokenpair_id = create_token_pair_id(DOT, ETH);
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, 3 * PLEDGE, 0, DEADLINE));
+		let user2 = create_user_with_two_assets(USER2, DOT, ETH, A_LOT);
+
+		// k per lp token squared, as a fraction, may never go down
+		let k_per_lp = || {
+			let info = crate::Pools::<Test>::get(tokenpair_id).unwrap();
+			(u512(info.reserve_a) * u512(info.reserve_b), u512(info.lp_supply) * u512(info.lp_supply))
+		};
+		let mut last = k_per_lp();
+
+		// Loop tiny trades and deposit-withdraw round trips in both directions
+		let mut random = Samples(0x9e37_79b9_7f4a_7c15_2545_f491_4f6c_dd1d);
+		for i in 0..300 {
+			let amount = 1_000 + random.next() % 10_000;
+			if i % 2 == 0 {
+				assert_ok!(TemplateModule::swap(Origin::signed(user2), DOT, ETH, amount, 0, DEADLINE));
+			} else {
+				assert_ok!(TemplateModule::swap(Origin::signed(user2), ETH, DOT, amount, 0, DEADLINE));
+			}
+			if i % 3 == 0 {
+				let before = (Assets::balance(DOT, &user2), Assets::balance(ETH, &user2));
+				assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user2), DOT, ETH, amount, 4 * amount, 0, DEADLINE));
+				let lp_tokens = Assets::balance(lp_token_id, &user2);
+				assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user2), DOT, ETH, lp_token_id, lp_tokens, 0, 0, DEADLINE));
+				assert!(Assets::balance(DOT, &user2) <= before.0);
+				assert!(Assets::balance(ETH, &user2) <= before.1);
+			}
+			let now = k_per_lp();
+			assert!(now.0 * last.1 >= last.0 * now.1);
+			last = now;
+		}
+
+		// The pool holds at least its reserves, so everybody can still withdraw
+		let info = crate::Pools::<Test>::get(tokenpair_id).unwrap();
+		assert!(Assets::balance(DOT, &pool_account(DOT, ETH)) >= info.reserve_a);
+		assert!(Assets::balance(ETH, &pool_account(DOT, ETH)) >= info.reserve_b);
+		let lp_tokens = Assets::balance(lp_token_id, &user);
+		assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, lp_token_id, lp_tokens, 0, 0, DEADLINE));
+    });
+}
+
+#[test]
+fn test_swap_pricing_keeps_k() {
+	let reserves: (u128, u128) = (PLEDGE, 3 * PLEDGE);
+	for fee in [Permill::zero(), Permill::from_perthousand(3), Permill::from_percent(50)] {
+		for amount in [1_000u128, 7_777, NOT_ENOUGH, A_LOT] {
+			let (out, fee_amount) = DexPricer::swap(amount, reserves, fee).unwrap();
+			assert_eq!(fee_amount, fee.mul_ceil(amount));
+			assert!(out < reserves.1);
+			assert!((reserves.0 + amount) * (reserves.1 - out) >= reserves.0 * reserves.1);
+		}
+	}
+	// Nothing in, or nothing to take out
+	assert_eq!(DexPricer::swap(0u128, reserves, Permill::zero()), Err(PricerError::InsufficientInputAmount));
+	assert_eq!(DexPricer::swap(PLEDGE, (PLEDGE, 0u128), Permill::zero()), Err(PricerError::ZeroLiquidity));
+	// Too little to get anything out
+	assert_eq!(DexPricer::swap(1u128, (A_LOT, 1u128), Permill::zero()), Err(PricerError::InsufficientOutputAmount));
+}
+
+#[test]
+fn test_swap_fee_stays_in_pool() {
+    new_test_ext().execute_with(|| {
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		let pool = pool_account(DOT, ETH);
+		let k_before = Assets::balance(DOT, &pool) * Assets::balance(ETH, &pool);
+
+		// Swapping back and forth only leaves fees behind
+		assert_ok!(TemplateModule::swap(Origin::signed(user), DOT, ETH, NOT_ENOUGH, 0, DEADLINE));
+		let eth_received = Assets::balance(ETH, &user) - (A_LOT - PLEDGE);
+		assert_ok!(TemplateModule::swap(Origin::signed(user), ETH, DOT, eth_received, 0, DEADLINE));
+		assert!(Assets::balance(DOT, &user) < A_LOT - PLEDGE);
+		assert!(Assets::balance(DOT, &pool) * Assets::balance(ETH, &pool) > k_before);
+    });
+}
+
+#[test]
+fn test_protocol_fee_pricing() {
+	// Same as Uniswap v2 with a 1/6 share: 1_000 * 100 / (5 * 1_100 + 1_000)
+	let share = Permill::from_rational(1u32, 6u32);
+	assert_eq!(DexPricer::protocol_fee(1_000u128, 1_100, 1_000, share), Ok(15));
+	// No growth, no fee
+	assert_eq!(DexPricer::protocol_fee(1_000u128, 1_000, 1_000, share), Ok(0));
+	assert_eq!(DexPricer::protocol_fee(1_000u128, 1_100, 1_000, Permill::zero()), Ok(0));
+}
+
+#[test]
+fn test_protocol_fee_minted_to_receiver() {
+    new_test_ext().execute_with(|| {
+		let lp_token_id = LpTokenIdStart::get();
+		let share = Permill::from_rational(1u32, 6u32);
+		assert_noop!(TemplateModule::set_protocol_fee(Origin::signed(USER), share), DispatchError::BadOrigin);
+		assert_ok!(TemplateModule::set_protocol_fee(Origin::root(), share));
+
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		assert_eq!(Assets::balance(lp_token_id, &TreasuryAccount::get()), 0);
+
+		// Fees of the swaps grow k, the protocol gets its share on the next deposit
+		assert_ok!(TemplateModule::swap(Origin::signed(user), DOT, ETH, NOT_ENOUGH, 0, DEADLINE));
+		assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		assert!(Assets::balance(lp_token_id, &TreasuryAccount::get()) > 0);
+
+		// Switched off, nothing is minted anymore
+		assert_ok!(TemplateModule::set_protocol_fee(Origin::root(), Permill::zero()));
+		let treasury_lp = Assets::balance(lp_token_id, &TreasuryAccount::get());
+		assert_ok!(TemplateModule::swap(Origin::signed(user), DOT, ETH, NOT_ENOUGH, 0, DEADLINE));
+		assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		assert_eq!(Assets::balance(lp_token_id, &TreasuryAccount::get()), treasury_lp);
+    });
+}
+
+#[test]
+fn test_deposit_matches_pool_ratio() {
+    new_test_ext().execute_with(|| {
+		let lp_token_id = LpTokenIdStart::get();
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, 2 * PLEDGE, 0, DEADLINE));
+
+		// Too much ETH, only the ETH matching the DOT is taken
+		let user2 = create_user_with_two_assets(USER2, DOT, ETH, A_LOT);
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user2), DOT, ETH, PLEDGE, 3 * PLEDGE, 0, DEADLINE));
+		assert_eq!(Assets::balance(DOT, &user2), A_LOT - PLEDGE);
+		assert_eq!(Assets::balance(ETH, &user2), A_LOT - 2 * PLEDGE);
+		assert_eq!(Assets::balance(lp_token_id, &user2), Assets::balance(lp_token_id, &user) + MinimumLiquidity::get());
+
+		// Too much DOT, only the DOT matching the ETH is taken
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user2), ETH, DOT, PLEDGE, PLEDGE, 0, DEADLINE));
+		assert_eq!(Assets::balance(DOT, &user2), A_LOT - PLEDGE - PLEDGE / 2);
+		assert_eq!(Assets::balance(ETH, &user2), A_LOT - 3 * PLEDGE);
+    });
+}
+
+#[test]
+fn test_optimal_deposit_pricing() {
+	let reserves: (u128, u128) = (PLEDGE, 2 * PLEDGE);
+	assert_eq!(DexPricer::quote(1_000u128, reserves), Ok(2_000));
+	assert_eq!(DexPricer::optimal_deposit((1_000u128, 5_000), reserves), Ok((1_000, 2_000)));
+	assert_eq!(DexPricer::optimal_deposit((1_000u128, 1_000), reserves), Ok((500, 1_000)));
+	assert_eq!(DexPricer::optimal_deposit((1_000u128, 1_000), (0, 0)), Err(PricerError::ZeroLiquidity));
+	// The limiting side decides the lp reward
+	assert_eq!(DexPricer::existing_pool_function((1_000u128, 5_000), reserves, PLEDGE), Ok(1_000));
+}
+
+#[test]
+fn test_deadlinepassed_error() {
+    new_test_ext().execute_with(|| {
+		System::set_block_number(DEADLINE + 1);
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE), Error::<Test>::DeadlinePassed);
+		assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, LP, PLEDGE, 0, 0, DEADLINE), Error::<Test>::DeadlinePassed);
+		assert_noop!(TemplateModule::swap(Origin::signed(user), DOT, ETH, PLEDGE, 0, DEADLINE), Error::<Test>::DeadlinePassed);
+		assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+		assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE + 1));
+    });
+}
+
+#[test]
+fn test_slippageexceeded_error() {
+    new_test_ext().execute_with(|| {
+		let lp_token_id = LpTokenIdStart::get();
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+		// sqrt(PLEDGE * PLEDGE) lp tokens for the first deposit, minus the locked minimum liquidity
+		assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, PLEDGE - MinimumLiquidity::get() + 1, DEADLINE), Error::<Test>::SlippageExceeded);
+		assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, PLEDGE - MinimumLiquidity::get(), DEADLINE));
+		let user2 = create_user_with_two_assets(USER2, DOT, ETH, A_LOT);
+		assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user2), DOT, ETH, PLEDGE, PLEDGE, PLEDGE, DEADLINE));
+
+		// 48_853_000 * 100_000_000 / (100_000_000 + 48_853_000) after the 0.3% fee
+		assert_noop!(TemplateModule::swap(Origin::signed(user), DOT, ETH, NOT_ENOUGH, 32_819_628, DEADLINE), Error::<Test>::SlippageExceeded);
+		assert_ok!(TemplateModule::swap(Origin::signed(user), DOT, ETH, NOT_ENOUGH, 32_819_627, DEADLINE));
+
+		// Half of the pool is 74_500_000 DOT and 33_590_186 ETH, the minimums follow the argument order
+		assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(user), ETH, DOT, lp_token_id, PLEDGE - MinimumLiquidity::get(), PLEDGE, 0, DEADLINE), Error::<Test>::SlippageExceeded);
+		assert_noop!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, lp_token_id, PLEDGE - MinimumLiquidity::get(), 0, PLEDGE, DEADLINE), Error::<Test>::SlippageExceeded);
+		assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user), ETH, DOT, lp_token_id, PLEDGE - MinimumLiquidity::get(), 0, PLEDGE, DEADLINE));
+    });
+}
+
+#[test]
+fn test_swap_exact_out_pricing() {
+	let reserves: (u128, u128) = (PLEDGE, 3 * PLEDGE);
+	for fee in [Permill::zero(), Permill::from_perthousand(3), Permill::from_percent(50)] {
+		for amount_out in [1u128, 7_777, NOT_ENOUGH, 3 * PLEDGE - 1] {
+			let (amount_in, fee_amount) = DexPricer::swap_exact_out(amount_out, reserves, fee).unwrap();
+			assert!(fee_amount <= amount_in);
+			assert!((reserves.0 + amount_in - fee_amount) * (reserves.1 - amount_out) >= reserves.0 * reserves.1);
+		}
+	}
+	// Nothing out, or everything out
+	assert_eq!(DexPricer::swap_exact_out(0u128, reserves, Permill::zero()), Err(PricerError::InsufficientOutputAmount));
+	assert_eq!(DexPricer::swap_exact_out(reserves.1, reserves, Permill::zero()), Err(PricerError::ReserveDrained));
+}
+
+#[test]
+fn test_swap_tokens_for_exact_tokens() {
+    new_test_ext().execute_with(|| {
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+		assert_noop!(TemplateModule::swap_tokens_for_exact_tokens(Origin::signed(user), DOT, ETH, NOT_ENOUGH, A_LOT, DEADLINE), Error::<Test>::PoolNotFound);
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		assert_noop!(TemplateModule::swap_tokens_for_exact_tokens(Origin::signed(user), DOT, ETH, PLEDGE, A_LOT, DEADLINE), Error::<Test>::ReserveDrained);
+
+		// 50_000_000 * 25_000_000 / 25_000_000 before the fee, / 0.997 after the fee
+		let amount_in = 50_150_452;
+		assert_noop!(TemplateModule::swap_tokens_for_exact_tokens(Origin::signed(user), DOT, ETH, PLEDGE / 2, amount_in - 1, DEADLINE), Error::<Test>::SlippageExceeded);
+		assert_ok!(TemplateModule::swap_tokens_for_exact_tokens(Origin::signed(user), DOT, ETH, PLEDGE / 2, amount_in, DEADLINE));
+		assert_eq!(Assets::balance(DOT, &user), A_LOT - PLEDGE - amount_in);
+		assert_eq!(Assets::balance(ETH, &user), A_LOT - PLEDGE / 2);
+    });
+}
+
+#[test]
+fn test_swap_along_path() {
+    new_test_ext().execute_with(|| {
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+		let user = create_user_with_one_asset(user, BTC, A_LOT);
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), ETH, BTC));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), ETH, BTC, PLEDGE, 2 * PLEDGE, 0, DEADLINE));
+
+		let path = |tokens: Vec<u32>| -> BoundedVec<u32, ConstU32<4>> { tokens.try_into().unwrap() };
+		assert_noop!(TemplateModule::swap_along_path(Origin::signed(user), path(vec![DOT]), PLEDGE, 0, DEADLINE), Error::<Test>::InvalidPath);
+		assert_noop!(TemplateModule::swap_along_path(Origin::signed(user), path(vec![DOT, ETH, DOT]), PLEDGE, 0, DEADLINE), Error::<Test>::InvalidPath);
+		assert_noop!(TemplateModule::swap_along_path(Origin::signed(user), path(vec![DOT, NOASSET2]), PLEDGE, 0, DEADLINE), Error::<Test>::InvalidToken);
+		assert_noop!(TemplateModule::swap_along_path(Origin::signed(user), path(vec![DOT, ADA, BTC]), PLEDGE, 0, DEADLINE), Error::<Test>::PoolNotFound);
+
+		// DOT -> ETH -> BTC priced hop by hop
+		let fee = Permill::from_perthousand(3);
+		let (eth_out, _) = DexPricer::swap(NOT_ENOUGH, (PLEDGE, PLEDGE), fee).unwrap();
+		let (btc_out, _) = DexPricer::swap(eth_out, (PLEDGE, 2 * PLEDGE), fee).unwrap();
+		assert_noop!(TemplateModule::swap_along_path(Origin::signed(user), path(vec![DOT, ETH, BTC]), NOT_ENOUGH, btc_out + 1, DEADLINE), Error::<Test>::SlippageExceeded);
+		assert_ok!(TemplateModule::swap_along_path(Origin::signed(user), path(vec![DOT, ETH, BTC]), NOT_ENOUGH, btc_out, DEADLINE));
+
+		// ETH only passed from pool to pool
+		assert_eq!(Assets::balance(DOT, &user), A_LOT - PLEDGE - NOT_ENOUGH);
+		assert_eq!(Assets::balance(ETH, &user), A_LOT - 2 * PLEDGE);
+		assert_eq!(Assets::balance(BTC, &user), A_LOT - 2 * PLEDGE + btc_out);
+		assert_eq!(Assets::balance(ETH, &pool_account(ETH, BTC)), PLEDGE + eth_out);
+    });
+}
+
+#[test]
+fn test_asset_registry() {
+    new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		assert_noop!(TemplateModule::add_asset(Origin::signed(USER), NOASSET2), DispatchError::BadOrigin);
+		assert_noop!(TemplateModule::add_asset(Origin::root(), DOT), Error::<Test>::AssetAlreadyListed);
+		assert_noop!(TemplateModule::remove_asset(Origin::root(), NOASSET2), Error::<Test>::AssetNotListed);
+		assert_noop!(TemplateModule::set_withdraw_only(Origin::root(), NOASSET2, true), Error::<Test>::AssetNotListed);
+
+		// A new asset can be used right after listing
+		let user = create_user_with_two_assets(USER, DOT, NOASSET2, A_LOT);
+		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, NOASSET2, PLEDGE, PLEDGE, 0, DEADLINE), Error::<Test>::InvalidToken);
+		assert_ok!(TemplateModule::add_asset(Origin::root(), NOASSET2));
+		System::assert_last_event(Event::TemplateModule(crate::Event::<Test>::AssetListed { asset: NOASSET2 }));
+		assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, NOASSET2));
+		assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, NOASSET2, PLEDGE, PLEDGE, 0, DEADLINE));
+		let user2 = create_user_with_two_assets(USER2, DOT, NOASSET2, A_LOT);
+		assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user2), DOT, NOASSET2, PLEDGE, PLEDGE, 0, DEADLINE));
+
+		// Withdraw-only assets can't be deposited or swapped anymore, but can be withdrawn
+		assert_ok!(TemplateModule::set_withdraw_only(Origin::root(), NOASSET2, true));
+		assert_noop!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, NOASSET2, PLEDGE, PLEDGE, 0, DEADLINE), Error::<Test>::TokenWithdrawOnly);
+		assert_noop!(TemplateModule::swap(Origin::signed(user), DOT, NOASSET2, PLEDGE, 0, DEADLINE), Error::<Test>::TokenWithdrawOnly);
+		let lp_token_id = LpTokenIdStart::get();
+		assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, NOASSET2, lp_token_id, PLEDGE - MinimumLiquidity::get(), 0, 0, DEADLINE));
+
+		// Assets of a pool can't be delisted, that would lock its liquidity
+		assert_eq!(crate::AssetPools::<Test>::get(NOASSET2), 1);
+		assert_noop!(TemplateModule::remove_asset(Origin::root(), NOASSET2), Error::<Test>::AssetInUse);
+		assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user2), DOT, NOASSET2, lp_token_id, PLEDGE, 0, 0, DEADLINE));
+
+		// Delisted assets can't be used at all
+		assert_ok!(TemplateModule::remove_asset(Origin::root(), BTC));
+		System::assert_last_event(Event::TemplateModule(crate::Event::<Test>::AssetDelisted { asset: BTC }));
+		assert_noop!(TemplateModule::create_pool(Origin::signed(user), DOT, BTC), Error::<Test>::InvalidToken);
+    });
+}
+
+#[test]
+fn test_migrate_to_v1() {
+    new_test_ext().execute_with(|| {
+		// A pool as v0 left it, its account holds a copy of every lp token given out
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+		let tokenpair_id = create_token_pair_id(DOT, ETH);
+		let pool = pool_account(DOT, ETH);
+		let lp_token_id = u32::decode(&mut &tokenpair_id[..]).unwrap();
+		Balances::make_free_balance_be(&pool, 1_000);
+		assert_ok!(Assets::force_create(Origin::root(), lp_token_id, pool, true, 1));
+		assert_ok!(Assets::transfer(Origin::signed(user), DOT, pool, PLEDGE));
+		assert_ok!(Assets::transfer(Origin::signed(user), ETH, pool, 2 * PLEDGE));
+		assert_ok!(Assets::mint_into(lp_token_id, &user, PLEDGE));
+		assert_ok!(Assets::mint_into(lp_token_id, &pool, PLEDGE));
+		put_storage_value(b"TemplateModule", b"AllPools", &tokenpair_id.blake2_128_concat(), pool);
+		put_storage_value(b"TemplateModule", b"LiquidityProviders", &pool.blake2_128_concat(), vec![user]);
+		StorageVersion::new(0).put::<TemplateModule>();
+
+		<TemplateModule as OnRuntimeUpgrade>::on_runtime_upgrade();
+		assert_eq!(TemplateModule::on_chain_storage_version(), 1);
+		let migrated = crate::PoolInfo {
+			token_a: DOT,
+			token_b: ETH,
+			account: pool,
+			reserve_a: PLEDGE,
+			reserve_b: 2 * PLEDGE,
+			lp_token: lp_token_id,
+			lp_supply: PLEDGE,
+			fee: SwapFee::get(),
+			created_at: System::block_number(),
+		};
+		assert_eq!(crate::Pools::<Test>::get(tokenpair_id), Some(migrated.clone()));
+		assert_eq!((crate::AssetPools::<Test>::get(DOT), crate::AssetPools::<Test>::get(ETH)), (1, 1));
+		assert_eq!(Assets::balance(lp_token_id, &pool), 0);
+		assert_eq!(get_storage_value::<AccountId>(b"TemplateModule", b"AllPools", &tokenpair_id.blake2_128_concat()), None);
+		assert_eq!(get_storage_value::<Vec<AccountId>>(b"TemplateModule", b"LiquidityProviders", &pool.blake2_128_concat()), None);
+
+		// Migrating again changes nothing
+		<TemplateModule as OnRuntimeUpgrade>::on_runtime_upgrade();
+		assert_eq!(crate::Pools::<Test>::get(tokenpair_id), Some(migrated));
+
+		// The lp tokens of the providers work with the migrated pool
+		assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, lp_token_id, PLEDGE / 2, 0, 0, DEADLINE));
+		assert_eq!(Assets::balance(ETH, &user), A_LOT - PLEDGE);
+    });
+}
+
+#[test]
+fn test_migrate_to_v1_keeps_unlisted_pools() {
+    new_test_ext().execute_with(|| {
+		// A v0 pool of a token that isn't listed on this chain
+		let tokenpair_id = create_token_pair_id(DOT, NOASSET2);
+		let pool = pool_account(DOT, NOASSET2);
+		put_storage_value(b"TemplateModule", b"AllPools", &tokenpair_id.blake2_128_concat(), pool);
+		StorageVersion::new(0).put::<TemplateModule>();
+
+		// It stays where it is until its assets are listed
+		<TemplateModule as OnRuntimeUpgrade>::on_runtime_upgrade();
+		assert_eq!(TemplateModule::on_chain_storage_version(), 0);
+		assert_eq!(crate::Pools::<Test>::get(tokenpair_id), None);
+		assert_eq!(get_storage_value::<AccountId>(b"TemplateModule", b"AllPools", &tokenpair_id.blake2_128_concat()), Some(pool));
+
+		assert_ok!(TemplateModule::add_asset(Origin::root(), NOASSET2));
+		<TemplateModule as OnRuntimeUpgrade>::on_runtime_upgrade();
+		assert_eq!(TemplateModule::on_chain_storage_version(), 1);
+		assert_eq!(crate::Pools::<Test>::get(tokenpair_id).map(|info| info.account), Some(pool));
+		assert_eq!(get_storage_value::<AccountId>(b"TemplateModule", b"AllPools", &tokenpair_id.blake2_128_concat()), None);
+    });
+}
+
+#[test]
+fn test_try_state() {
+    new_test_ext().execute_with(|| {
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+		let tokenpair_id = create_token_pair_id(DOT, ETH);
+		assert_ok!(TemplateModule::set_protocol_fee(Origin::root(), Permill::from_percent(20)));
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+
+		// Swaps, deposits, withdrawals and the protocol fee keep the value of an lp token
+		let snapshot = TemplateModule::pool_snapshot().unwrap();
+		assert_ok!(TemplateModule::swap(Origin::signed(user), DOT, ETH, PLEDGE / 10, 0, DEADLINE));
+		assert_ok!(TemplateModule::do_try_state(&snapshot));
+		let snapshot = TemplateModule::pool_snapshot().unwrap();
+		assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		assert_ok!(TemplateModule::swap(Origin::signed(user), ETH, DOT, PLEDGE / 10, 0, DEADLINE));
+		assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, LpTokenIdStart::get(), PLEDGE / 10, 0, 0, DEADLINE));
+		assert_ok!(TemplateModule::do_try_state(&snapshot));
+
+		// Broken pools are caught
+		let snapshot = TemplateModule::pool_snapshot().unwrap();
+		let pool = crate::Pools::<Test>::get(tokenpair_id).unwrap();
+		crate::Pools::<Test>::mutate(tokenpair_id, |p| p.as_mut().unwrap().reserve_a -= 1);
+		assert_eq!(TemplateModule::do_try_state(&snapshot), Err("k per lp token decreased"));
+		crate::Pools::<Test>::mutate(tokenpair_id, |p| p.as_mut().unwrap().reserve_a += 2);
+		assert_eq!(TemplateModule::do_try_state(&snapshot), Err("reserves exceed the pool's balances"));
+		crate::Pools::<Test>::insert(tokenpair_id, crate::PoolInfo { lp_supply: pool.lp_supply + 1, ..pool.clone() });
+		assert_eq!(TemplateModule::do_try_state(&snapshot), Err("lp supply drifted from the lp token"));
+		crate::Pools::<Test>::insert(tokenpair_id, pool.clone());
+		assert_ok!(TemplateModule::do_try_state(&snapshot));
+
+		// A withdrawal doesn't cover a pool losing value
+		assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, LpTokenIdStart::get(), PLEDGE / 10, 0, 0, DEADLINE));
+		crate::Pools::<Test>::mutate(tokenpair_id, |p| p.as_mut().unwrap().reserve_a -= PLEDGE / 100);
+		assert_eq!(TemplateModule::do_try_state(&snapshot), Err("k per lp token decreased"));
+		crate::Pools::<Test>::mutate(tokenpair_id, |p| p.as_mut().unwrap().reserve_a += PLEDGE / 100);
+		assert_ok!(TemplateModule::do_try_state(&snapshot));
+
+		crate::AllowedAssets::<Test>::remove(ETH);
+		assert_eq!(TemplateModule::do_try_state(&snapshot), Err("pool uses an asset that isn't listed"));
+    });
+}
+
+#[test]
+fn test_try_state_every_block() {
+    new_test_ext().execute_with(|| {
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+		assert_ok!(TemplateModule::set_protocol_fee(Origin::root(), Permill::from_percent(20)));
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		run_to_block(2);
+
+		// Every block ends with the pools checked against the end of the last one
+		assert_ok!(TemplateModule::swap(Origin::signed(user), DOT, ETH, PLEDGE / 10, 0, DEADLINE));
+		run_to_block(3);
+		assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		assert_ok!(TemplateModule::swap(Origin::signed(user), ETH, DOT, PLEDGE / 10, 0, DEADLINE));
+		assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user), DOT, ETH, LpTokenIdStart::get(), PLEDGE / 10, 0, 0, DEADLINE));
+		run_to_block(4);
+    });
+}
+
+#[test]
+#[should_panic(expected = "k per lp token decreased")]
+fn test_try_state_catches_a_block_losing_value() {
+    new_test_ext().execute_with(|| {
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, 0, DEADLINE));
+		run_to_block(2);
+
+		crate::Pools::<Test>::mutate(create_token_pair_id(DOT, ETH), |p| p.as_mut().unwrap().reserve_a -= 1);
+		run_to_block(3);
+    });
+}
+
+#[test]
+#[should_panic(expected = "genesis assets can't use the IDs reserved for lp tokens")]
+fn test_genesis_reserved_asset_id() {
+	let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	crate::GenesisConfig::<Test> { allowed_assets: vec![DOT, LpTokenIdStart::get()], pools: vec![] }
+		.assimilate_storage(&mut t)
+		.unwrap();
+}
+
+#[test]
+fn test_genesis_pools() {
+	// The provider's native and tokens have to be there before the pools are built
+	let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	pallet_balances::GenesisConfig::<Test> { balances: vec![(USER, A_LOT)] }.assimilate_storage(&mut t).unwrap();
+	pallet_assets::GenesisConfig::<Test> {
+		assets: vec![(DOT, USER, true, 1), (ETH, USER, true, 1)],
+		metadata: vec![],
+		accounts: vec![(DOT, USER, A_LOT), (ETH, USER, A_LOT)],
+	}
+	.assimilate_storage(&mut t)
+	.unwrap();
+	crate::GenesisConfig::<Test> { allowed_assets: vec![DOT, ETH], pools: vec![(ETH, DOT, 2 * PLEDGE, PLEDGE, USER)] }
+		.assimilate_storage(&mut t)
+		.unwrap();
+
+	sp_io::TestExternalities::from(t).execute_with(|| {
+		let lp_token_id = LpTokenIdStart::get();
+		let pool = crate::Pools::<Test>::get(create_token_pair_id(DOT, ETH)).unwrap();
+		assert_eq!((pool.token_a, pool.token_b), (DOT, ETH));
+		assert_eq!((pool.reserve_a, pool.reserve_b), (PLEDGE, 2 * PLEDGE));
+		assert_eq!(pool.account, pool_account(DOT, ETH));
+		assert_eq!(pool.lp_token, lp_token_id);
+		assert_eq!(pool.lp_supply, 70_710_678);
+		assert_eq!(Assets::balance(lp_token_id, &USER), 70_710_678 - MinimumLiquidity::get());
+		assert_eq!(Assets::balance(DOT, &USER), A_LOT - PLEDGE);
+		assert_eq!(Balances::reserved_balance(&USER), PoolCreationDeposit::get());
+
+		// The pool works like any other
+		assert_ok!(TemplateModule::swap(Origin::signed(USER), DOT, ETH, PLEDGE / 10, 0, DEADLINE));
+	});
+}
+
+#[test]
+fn test_quotes_match_extrinsics() {
+    new_test_ext().execute_with(|| {
+		let user = create_user_with_two_assets(USER, DOT, ETH, A_LOT);
+		let lp_token_id = LpTokenIdStart::get();
+		assert_eq!(TemplateModule::get_reserves(DOT, ETH), Err(Error::<Test>::PoolNotFound.into()));
+        assert_ok!(TemplateModule::create_pool(Origin::signed(user), DOT, ETH));
+		assert_eq!(TemplateModule::pool_account(ETH, DOT), Ok(pool_account(DOT, ETH)));
+		assert_eq!(TemplateModule::lp_token(ETH, DOT), Ok(lp_token_id));
+		// The protocol fee makes later deposits and withdrawals mint lp tokens first
+		assert_ok!(TemplateModule::set_protocol_fee(Origin::root(), Permill::from_percent(20)));
+
+		// The quotes are in the order of the tokens given
+		let (amount_eth, amount_dot, lp_minted) = TemplateModule::quote_add_liquidity(ETH, DOT, 2 * PLEDGE, PLEDGE).unwrap();
+		assert_eq!((amount_eth, amount_dot, lp_minted), (2 * PLEDGE, PLEDGE, 70_710_678 - MinimumLiquidity::get()));
+        assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), ETH, DOT, 2 * PLEDGE, PLEDGE, 0, DEADLINE));
+		assert_eq!(Assets::balance(lp_token_id, &user), lp_minted);
+		assert_eq!(TemplateModule::get_reserves(ETH, DOT), Ok((2 * PLEDGE, PLEDGE)));
+
+		let amount_out = TemplateModule::quote_exact_in(DOT, ETH, PLEDGE / 10).unwrap();
+		let eth_before = Assets::balance(ETH, &user);
+		assert_ok!(TemplateModule::swap(Origin::signed(user), DOT, ETH, PLEDGE / 10, 0, DEADLINE));
+		assert_eq!(Assets::balance(ETH, &user), eth_before + amount_out);
+
+		let amount_in = TemplateModule::quote_exact_out(ETH, DOT, PLEDGE / 100).unwrap();
+		let eth_before = Assets::balance(ETH, &user);
+		assert_ok!(TemplateModule::swap_tokens_for_exact_tokens(Origin::signed(user), ETH, DOT, PLEDGE / 100, amount_in, DEADLINE));
+		assert_eq!(Assets::balance(ETH, &user), eth_before - amount_in);
+
+		let (amount_dot, amount_eth, lp_minted) = TemplateModule::quote_add_liquidity(DOT, ETH, PLEDGE, PLEDGE).unwrap();
+		let (dot_before, eth_before, lp_before) = (Assets::balance(DOT, &user), Assets::balance(ETH, &user), Assets::balance(lp_token_id, &user));
+		assert_ok!(TemplateModule::deposit_liquidity(Origin::signed(user), DOT, ETH, PLEDGE, PLEDGE, lp_minted, DEADLINE));
+		assert_eq!(Assets::balance(DOT, &user), dot_before - amount_dot);
+		assert_eq!(Assets::balance(ETH, &user), eth_before - amount_eth);
+		assert_eq!(Assets::balance(lp_token_id, &user), lp_before + lp_minted);
+
+		// Another swap, so the withdrawal mints the protocol fee as well
+		assert_ok!(TemplateModule::swap(Origin::signed(user), ETH, DOT, PLEDGE / 10, 0, DEADLINE));
+		let (amount_eth, amount_dot) = TemplateModule::quote_remove_liquidity(ETH, DOT, PLEDGE).unwrap();
+		let (dot_before, eth_before) = (Assets::balance(DOT, &user), Assets::balance(ETH, &user));
+		assert_ok!(TemplateModule::withdraw_liquidity(Origin::signed(user), ETH, DOT, lp_token_id, PLEDGE, amount_eth, amount_dot, DEADLINE));
+		assert_eq!(Assets::balance(DOT, &user), dot_before + amount_dot);
+		assert_eq!(Assets::balance(ETH, &user), eth_before + amount_eth);
+
+		// Trades the extrinsics would reject can't be quoted
+		let (reserve_dot, _) = TemplateModule::get_reserves(DOT, ETH).unwrap();
+		assert_eq!(TemplateModule::quote_exact_out(ETH, DOT, reserve_dot), Err(Error::<Test>::ReserveDrained.into()));
+		assert_eq!(TemplateModule::quote_exact_in(DOT, ADA, PLEDGE), Err(Error::<Test>::PoolNotFound.into()));
+		assert_eq!(TemplateModule::quote_remove_liquidity(DOT, ETH, 0), Err(Error::<Test>::NoTokens.into()));
+
+		// Withdraw-only tokens can only be quoted out of their pools
+		assert_ok!(TemplateModule::set_withdraw_only(Origin::root(), ETH, true));
+		assert_eq!(TemplateModule::quote_exact_in(DOT, ETH, PLEDGE), Err(Error::<Test>::TokenWithdrawOnly.into()));
+		assert_eq!(TemplateModule::quote_exact_out(DOT, ETH, PLEDGE), Err(Error::<Test>::TokenWithdrawOnly.into()));
+		assert_eq!(TemplateModule::quote_add_liquidity(DOT, ETH, PLEDGE, PLEDGE), Err(Error::<Test>::TokenWithdrawOnly.into()));
+		assert!(TemplateModule::quote_remove_liquidity(DOT, ETH, PLEDGE).is_ok());
+		assert_eq!(TemplateModule::quote_exact_in(DOT, NOASSET2, PLEDGE), Err(Error::<Test>::InvalidToken.into()));
     });
-}
\ No newline at end of file
+}
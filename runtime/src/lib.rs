@@ -103,7 +103,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	//   `spec_version`, and `authoring_version` are the same between Wasm and native.
 	// This value is set to 100 to notify Polkadot-JS App (https://polkadot.js.org/apps) to use
 	//   the compatible custom types.
-	spec_version: 100,
+	spec_version: 101,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
 	transaction_version: 1,
@@ -268,17 +268,62 @@ impl pallet_sudo::Config for Runtime {
 	type Call = Call;
 }
 
+parameter_types! {
+	pub const TreasuryPalletId: PalletId = PalletId(*b"py/trsry");
+	pub const ProposalBond: Permill = Permill::from_percent(5);
+	pub const ProposalBondMinimum: Balance = 1_000_000;
+	pub const SpendPeriod: BlockNumber = DAYS;
+	pub const Burn: Permill = Permill::zero();
+}
+
+impl pallet_treasury::Config for Runtime {
+	type PalletId = TreasuryPalletId;
+	type Currency = Balances;
+	type ApproveOrigin = EnsureRoot<AccountId>;
+	type RejectOrigin = EnsureRoot<AccountId>;
+	type Event = Event;
+	type OnSlash = ();
+	type ProposalBond = ProposalBond;
+	type ProposalBondMinimum = ProposalBondMinimum;
+	type ProposalBondMaximum = ();
+	type SpendPeriod = SpendPeriod;
+	type Burn = Burn;
+	type BurnDestination = ();
+	type SpendFunds = ();
+	type WeightInfo = pallet_treasury::weights::SubstrateWeight<Runtime>;
+	type MaxApprovals = ConstU32<100>;
+}
+
 parameter_types! {
 	pub const DEX_pallet: PalletId = PalletId(*b"DEX_POOL");
+	/// 0.3% of every swap goes to the liquidity providers.
+	pub const SwapFee: Permill = Permill::from_perthousand(3);
+	/// The protocol's share of the swap fees goes to the treasury.
+	pub TreasuryAccount: AccountId = Treasury::account_id();
+	/// Reserved from the creator of a pool.
+	pub const PoolCreationDeposit: Balance = 100_000;
+	/// Lp tokens locked in every pool by its first deposit.
+	pub const MinimumLiquidity: Balance = 1_000;
+	/// The upper half of the asset IDs is reserved for the lp tokens.
+	pub const LpTokenIdStart: u32 = 1 << 31;
 }
 
 /// Configure the pallet-template in pallets/template.
 impl pallet_template::Config for Runtime {
 	type Event = Event;
 	type Tokens = Assets;
+	type AssetId = u32;
+	type AssetBalance = Balance;
 	type Balances = Balances;
 	type PalletId = DEX_pallet;
-	type MaxLiqProviders = frame_support::pallet_prelude::ConstU32<4>;
+	type SwapFee = SwapFee;
+	type PoolCreationDeposit = PoolCreationDeposit;
+	type MinimumLiquidity = MinimumLiquidity;
+	type LpTokenIdStart = LpTokenIdStart;
+	type MaxLpTokenIdSkips = ConstU32<16>;
+	type MaxPathLength = ConstU32<4>;
+	type ProtocolFeeReceiver = TreasuryAccount;
+	type AdminOrigin = EnsureRoot<AccountId>;
 }
 
 parameter_types! {
@@ -323,6 +368,7 @@ construct_runtime!(
 		Sudo: pallet_sudo,
 		Assets: pallet_assets,
 		Balances: pallet_balances,
+		Treasury: pallet_treasury,
 		// Include the custom logic from the pallet-template in the runtime.
 		TemplateModule: pallet_template,
 	}
@@ -504,6 +550,41 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl pallet_template::runtime_api::DexApi<Block, AccountId, u32, Balance> for Runtime {
+		fn get_reserves(token_a: u32, token_b: u32) -> Option<(Balance, Balance)> {
+			TemplateModule::get_reserves(token_a, token_b).ok()
+		}
+
+		fn quote_exact_in(token_in: u32, token_out: u32, amount_in: Balance) -> Option<Balance> {
+			TemplateModule::quote_exact_in(token_in, token_out, amount_in).ok()
+		}
+
+		fn quote_exact_out(token_in: u32, token_out: u32, amount_out: Balance) -> Option<Balance> {
+			TemplateModule::quote_exact_out(token_in, token_out, amount_out).ok()
+		}
+
+		fn quote_add_liquidity(
+			token_a: u32,
+			token_b: u32,
+			amount_a: Balance,
+			amount_b: Balance,
+		) -> Option<(Balance, Balance, Balance)> {
+			TemplateModule::quote_add_liquidity(token_a, token_b, amount_a, amount_b).ok()
+		}
+
+		fn quote_remove_liquidity(token_a: u32, token_b: u32, lp_amount: Balance) -> Option<(Balance, Balance)> {
+			TemplateModule::quote_remove_liquidity(token_a, token_b, lp_amount).ok()
+		}
+
+		fn pool_account(token_a: u32, token_b: u32) -> Option<AccountId> {
+			TemplateModule::pool_account(token_a, token_b).ok()
+		}
+
+		fn lp_token(token_a: u32, token_b: u32) -> Option<u32> {
+			TemplateModule::lp_token(token_a, token_b).ok()
+		}
+	}
+
 	#[cfg(feature = "runtime-benchmarks")]
 	impl frame_benchmarking::Benchmark<Block> for Runtime {
 		fn benchmark_metadata(extra: bool) -> (